@@ -0,0 +1,368 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module describes the board the chip is mounted on: where its LEDs and user button are
+//! connected, which USART is the console and on which pins, and the frequency of the HSE
+//! crystal if one is fitted.
+//!
+//! By default the kernel runs on a Nucleo-F042K6. To use another board, enable the
+//! `board_custom` feature and define the board in the application:
+//!
+//! ```
+//!   #[no_mangle]
+//!   pub fn application_board() -> &'static Board {
+//!       static MY_BOARD: MyBoard = MyBoard;
+//!       &MY_BOARD
+//!   }
+//! ```
+
+use peripheral::gpio::{GPIO, Group, Port, Mode, Type, Pull, Pin, Unknown, PinId};
+use peripheral::rcc;
+use altos_core::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+#[cfg(feature="serial")]
+use peripheral::gpio::{AF1, PA2, PA15};
+#[cfg(feature="serial")]
+use peripheral::gpio::af::{UsartTx, UsartRx};
+#[cfg(feature="serial")]
+use peripheral::usart::{self, UsartX, UsartId, UsartError, BaudRate, Usart2};
+
+/// The location of a pin on the chip.
+#[derive(Copy, Clone)]
+pub struct PinConfig {
+    /// The GPIO group the pin belongs to.
+    pub group: Group,
+    /// The pin's number within the group.
+    pub port: u8,
+}
+
+impl PinConfig {
+    /// Get the port for this pin.
+    ///
+    /// This is unsafe because it creates another owner of the pin, see `Port::new`.
+    #[doc(hidden)]
+    pub unsafe fn port(&self) -> Port {
+        Port::new(self.port, self.group)
+    }
+}
+
+/// An LED on the board.
+pub struct Led {
+    /// The pin driving the LED.
+    pub pin: PinConfig,
+    /// True if the LED lights up when the pin is driven low.
+    pub active_low: bool,
+}
+
+impl Led {
+    // UNSAFE: The caller makes sure nothing else is using the pin
+    unsafe fn init(&self) {
+        let mut port = self.pin.port();
+        port.set_type(Type::PushPull);
+        port.set_mode(Mode::Output);
+        set_lit(&mut port, false, self.active_low);
+    }
+}
+
+/// A push button on the board.
+pub struct Button {
+    /// The pin the button is connected to.
+    pub pin: PinConfig,
+    /// The pull resistor needed for the pin to read correctly when the button is released.
+    pub pull: Pull,
+    /// True if the pin reads low while the button is pressed.
+    pub active_low: bool,
+}
+
+impl Button {
+    // UNSAFE: The caller makes sure nothing else is using the pin
+    unsafe fn init(&self) {
+        let mut port = self.pin.port();
+        port.set_pull(self.pull);
+        port.set_mode(Mode::Input);
+    }
+}
+
+/// One of the board's LEDs, set up as an output. See `take_led`.
+pub struct BoardLed {
+    port: Port,
+    active_low: bool,
+}
+
+impl BoardLed {
+    /// Turn the LED on.
+    pub fn on(&mut self) {
+        set_lit(&mut self.port, true, self.active_low);
+    }
+
+    /// Turn the LED off.
+    pub fn off(&mut self) {
+        set_lit(&mut self.port, false, self.active_low);
+    }
+
+    /// Turn the LED on if it is off, and off if it is on.
+    pub fn toggle(&mut self) {
+        let lit = self.port.is_set_high() != self.active_low;
+        set_lit(&mut self.port, !lit, self.active_low);
+    }
+}
+
+fn set_lit(port: &mut Port, lit: bool, active_low: bool) {
+    if lit != active_low {
+        port.set();
+    }
+    else {
+        port.reset();
+    }
+}
+
+/// The board's user button, set up as an input. See `take_button`.
+pub struct BoardButton {
+    port: Port,
+    active_low: bool,
+}
+
+impl BoardButton {
+    /// Return true if the button is being pressed.
+    pub fn is_pressed(&self) -> bool {
+        self.port.is_high() != self.active_low
+    }
+}
+
+// A bit for each LED that has been taken, indexed by its position in `Board::leds`. The top bit
+// is for the button.
+static TAKEN: AtomicUsize = ATOMIC_USIZE_INIT;
+const BUTTON_INDEX: usize = 31;
+
+// Mark the LED or button at `index` as taken, returning false if it already was.
+fn take(index: usize) -> bool {
+    let bit = 0b1 << index;
+    TAKEN.fetch_or(bit, Ordering::Relaxed) & bit == 0
+}
+
+/// Take the board's LED at `index` in `Board::leds`.
+///
+/// This returns `None` if the board has no such LED, or if it has already been taken.
+pub fn take_led(index: usize) -> Option<BoardLed> {
+    let led = match board().leds().get(index) {
+        Some(led) if index < BUTTON_INDEX => led,
+        _ => return None,
+    };
+    if !take(index) {
+        return None;
+    }
+    // UNSAFE: The LED was just taken, so this is the only port for its pin
+    Some(BoardLed {
+        port: unsafe { led.pin.port() },
+        active_low: led.active_low,
+    })
+}
+
+/// Take the board's user button.
+///
+/// This returns `None` if the board has no button, or if it has already been taken.
+pub fn take_button() -> Option<BoardButton> {
+    let button = match board().button() {
+        Some(button) => button,
+        None => return None,
+    };
+    if !take(BUTTON_INDEX) {
+        return None;
+    }
+    // UNSAFE: The button was just taken, so this is the only port for its pin
+    Some(BoardButton {
+        port: unsafe { button.pin.port() },
+        active_low: button.active_low,
+    })
+}
+
+/// The pins the console USART is connected to, and the baud rate it runs at.
+#[cfg(feature="serial")]
+pub struct Console<TX, RX> {
+    /// The transmit pin.
+    pub tx: TX,
+    /// The receive pin.
+    pub rx: RX,
+    /// The baud rate the console runs at.
+    pub baud_rate: BaudRate,
+}
+
+#[cfg(feature="serial")]
+impl<TX, RX> Console<TX, RX> {
+    /// Open the console on Usart `U`, returning which Usart it is.
+    ///
+    /// The pins' types make sure they carry the signals of Usart `U`.
+    pub fn open<U: UsartId>(self) -> Result<UsartX, UsartError>
+        where TX: UsartTx<U>, RX: UsartRx<U> {
+
+        try!(usart::open::<U, TX, RX>(self.tx, self.rx, self.baud_rate));
+        Ok(U::usart())
+    }
+}
+
+// The ports the board has reserved in each GPIO group, one bit per port.
+static RESERVED_A: AtomicUsize = ATOMIC_USIZE_INIT;
+static RESERVED_B: AtomicUsize = ATOMIC_USIZE_INIT;
+static RESERVED_C: AtomicUsize = ATOMIC_USIZE_INIT;
+static RESERVED_F: AtomicUsize = ATOMIC_USIZE_INIT;
+
+fn reserved(group: Group) -> &'static AtomicUsize {
+    match group {
+        Group::A => &RESERVED_A,
+        Group::B => &RESERVED_B,
+        Group::C => &RESERVED_C,
+        Group::F => &RESERVED_F,
+    }
+}
+
+fn reserve(pin: &PinConfig) {
+    reserved(pin.group).fetch_or(0b1 << pin.port, Ordering::Relaxed);
+}
+
+/// Get the ports of `group` that the board uses for its console, LEDs and button, one bit per
+/// port. These pins are left out when the group is split.
+pub fn reserved_ports(group: Group) -> u16 {
+    reserved(group).load(Ordering::Relaxed) as u16
+}
+
+/// Create one of the pins the board reserves for the kernel, enabling its group.
+///
+/// The pin is left out when its group is split. This is unsafe because it must be called before
+/// the application takes the peripherals, and only once for each pin.
+pub unsafe fn reserved_pin<ID: PinId>() -> Pin<Unknown, ID> {
+    reserve(&PinConfig { group: ID::group(), port: ID::port() });
+    GPIO::enable(ID::group());
+    Pin::new_unchecked()
+}
+
+/// A description of the board the chip is mounted on.
+pub trait Board {
+    /// The LEDs on the board.
+    fn leds(&self) -> &'static [Led];
+
+    /// The user button, if the board has one.
+    fn button(&self) -> Option<&'static Button>;
+
+    /// Open the console USART on its pins, returning which USART it is.
+    ///
+    /// This is called once by the kernel while it starts up. The pins should come from
+    /// `reserved_pin`, and be opened with `Console::open`.
+    #[cfg(feature="serial")]
+    fn open_console(&self) -> Result<UsartX, UsartError>;
+
+    /// The frequency (in Hz) of the HSE crystal or external clock, if the board has one.
+    fn hse_frequency(&self) -> Option<u32>;
+}
+
+/// The Nucleo-F042K6 board, with its green LED on PB3 and the ST-Link virtual COM port on USART2.
+pub struct NucleoF042K6;
+
+static NUCLEO_F042K6: NucleoF042K6 = NucleoF042K6;
+
+static NUCLEO_F042K6_LEDS: [Led; 1] = [
+    Led { pin: PinConfig { group: Group::B, port: 3 }, active_low: false },
+];
+
+impl Board for NucleoF042K6 {
+    fn leds(&self) -> &'static [Led] {
+        &NUCLEO_F042K6_LEDS
+    }
+
+    fn button(&self) -> Option<&'static Button> {
+        // The Nucleo-32 boards only have a reset button
+        None
+    }
+
+    #[cfg(feature="serial")]
+    fn open_console(&self) -> Result<UsartX, UsartError> {
+        // UNSAFE: The ST-Link virtual COM port pins are reserved for the console
+        let (tx, rx) = unsafe { (reserved_pin::<PA2>(), reserved_pin::<PA15>()) };
+        Console {
+            tx: tx.into_alternate::<AF1>(),
+            rx: rx.into_alternate::<AF1>(),
+            baud_rate: BaudRate::Hz115200,
+        }.open::<Usart2>()
+    }
+
+    fn hse_frequency(&self) -> Option<u32> {
+        // No crystal is fitted by default
+        None
+    }
+}
+
+#[cfg(feature="board_custom")]
+extern "Rust" {
+    // The board description provided by the application
+    fn application_board() -> &'static Board;
+}
+
+/// Get the board the kernel is running on.
+pub fn board() -> &'static Board {
+    #[cfg(feature="board_custom")]
+    unsafe {
+        application_board()
+    }
+    #[cfg(not(feature="board_custom"))]
+    {
+        &NUCLEO_F042K6
+    }
+}
+
+/// Set up the board's LEDs and button, and tell the RCC the HSE frequency.
+#[doc(hidden)]
+pub fn init(board: &Board) {
+    if let Some(frequency) = board.hse_frequency() {
+        rcc::set_hse_frequency(frequency);
+    }
+
+    // UNSAFE: The board's pins are reserved, and nothing has taken them yet
+    for led in board.leds() {
+        reserve(&led.pin);
+        GPIO::enable(led.pin.group);
+        unsafe { led.init() };
+    }
+
+    if let Some(button) = board.button() {
+        reserve(&button.pin);
+        GPIO::enable(button.pin.group);
+        unsafe { button.init() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_led_can_only_be_taken_once() {
+        assert!(take_led(0).is_some());
+        assert!(take_led(0).is_none());
+    }
+
+    #[test]
+    fn test_reserved_ports_are_tracked_per_group() {
+        reserve(&PinConfig { group: Group::C, port: 13 });
+        reserve(&PinConfig { group: Group::C, port: 14 });
+        assert_eq!(reserved_ports(Group::C), 0b11 << 13);
+        assert_eq!(reserved_ports(Group::F), 0);
+    }
+
+    #[test]
+    fn test_missing_led_and_button_are_none() {
+        assert!(take_led(board().leds().len()).is_none());
+        assert!(take_button().is_none());
+    }
+}
@@ -30,8 +30,46 @@
 mod usart;
 #[cfg(feature="dma")]
 mod dma;
+pub mod svc;
 
 use altos_core::syscall;
+use altos_core::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use interrupt::{self, Hardware};
+use peripheral::rcc;
+use system_control;
+
+/// The action taken when an interrupt fires that has no handler.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UnhandledAction {
+    /// Report the interrupt and halt on a breakpoint.
+    Halt,
+    /// Report the interrupt, disable it in the NVIC and continue running.
+    ///
+    /// Only peripheral interrupts can be disabled, unhandled system exceptions will always halt.
+    Disable,
+}
+
+const UNHANDLED_HALT: usize = 0;
+const UNHANDLED_DISABLE: usize = 1;
+
+static UNHANDLED_ACTION: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Set the action taken when an interrupt fires that has no handler. By default the system halts.
+pub fn set_unhandled_action(action: UnhandledAction) {
+    let value = match action {
+        UnhandledAction::Halt => UNHANDLED_HALT,
+        UnhandledAction::Disable => UNHANDLED_DISABLE,
+    };
+    UNHANDLED_ACTION.store(value, Ordering::Relaxed);
+}
+
+/// Get the action taken when an interrupt fires that has no handler.
+pub fn get_unhandled_action() -> UnhandledAction {
+    match UNHANDLED_ACTION.load(Ordering::Relaxed) {
+        UNHANDLED_DISABLE => UnhandledAction::Disable,
+        _ => UnhandledAction::Halt,
+    }
+}
 
 // Interrupt vector table
 #[cfg(not(test))]
@@ -39,7 +77,7 @@ use altos_core::syscall;
 #[doc(hidden)]
 #[export_name="_EXCEPTIONS"]
 pub static EXCEPTIONS: [Option<unsafe extern "C" fn()>; 46] = [
-    Some(default_handler),  // NMI: 1
+    Some(nmi_handler),  // NMI: 1
     Some(hardfault_handler),  // Hard Fault: 2
     Some(default_handler),  // Memory Management Fault: 3
     Some(default_handler),  // Bus Fault: 4
@@ -53,46 +91,97 @@ pub static EXCEPTIONS: [Option<unsafe extern "C" fn()>; 46] = [
     None,                   // Reserved: 12
     Some(pend_sv_handler),  // PendSV: 13
     Some(systick_handler),  // SysTick: 14
-    Some(default_handler),  // Window Watchdog: 15
-    Some(default_handler),  // PVD_VDDIO2: 16
-    Some(default_handler),  // Real Time Clock: 17
-    Some(default_handler),  // Flash global: 18
-    Some(default_handler),  // RCC and CRS global: 19
-    Some(default_handler),  // EXTI Line[1:0]: 20
-    Some(default_handler),  // EXTI Line[3:2]: 21
-    Some(default_handler),  // EXTI Line[15:4]: 22
-    Some(default_handler),  // Touch Sensing: 23
-    Some(default_handler),  // DMA channel 1: 24
-    Some(default_handler),  // DMA channel 2 and 3 and DMA2 channel 1 and 2: 25
-    Some(dma_chan4plus_handler),  // DMA channel 4,5,6,7 and DMA2 channel 3,4,5: 26
-    Some(default_handler),  // ADC and COMP (ADC combined with EXTI lines 21 and 22): 27
-    Some(default_handler),  // TIM1 break, update, trigger, communication: 28
-    Some(default_handler),  // TIM1 capture compare: 29
-    Some(default_handler),  // TIM2 global: 30
-    Some(default_handler),  // TIM3 global: 31
-    Some(default_handler),  // TIM6 global and DAC underrun: 32
-    Some(default_handler),  // TIM7 global: 33
-    Some(default_handler),  // TIM14 global: 34
-    Some(default_handler),  // TIM15 global: 35
-    Some(default_handler),  // TIM16 global: 36
-    Some(default_handler),  // TIM17 global: 37
-    Some(default_handler),  // I2C1 global (combined with EXTI Line 23): 38
-    Some(default_handler),  // I2C2 global: 39
-    Some(default_handler),  // SPI1 global: 40
-    Some(default_handler),  // SPI2 global: 41
-    Some(default_handler),  // USART1 global (combined with EXTI Line 25): 42
-    Some(usart2_handler),  // USART2 global (combined with EXTI Line 26): 43
-    Some(default_handler),  // USART3,4,5,6,7,8 (combined with EXTI Line 28): 44
-    Some(default_handler),  // CEC and CAN global (combined with EXTI Line 27): 45
-    Some(default_handler),  // USB (combined with EXTI Line 18): 46
+    Some(irq_handler),      // Window Watchdog: 15
+    Some(irq_handler),      // PVD_VDDIO2: 16
+    Some(irq_handler),      // Real Time Clock: 17
+    Some(irq_handler),      // Flash global: 18
+    Some(irq_handler),      // RCC and CRS global: 19
+    Some(irq_handler),      // EXTI Line[1:0]: 20
+    Some(irq_handler),      // EXTI Line[3:2]: 21
+    Some(irq_handler),      // EXTI Line[15:4]: 22
+    Some(irq_handler),      // Touch Sensing: 23
+    Some(irq_handler),      // DMA channel 1: 24
+    Some(irq_handler),      // DMA channel 2 and 3 and DMA2 channel 1 and 2: 25
+    Some(irq_handler),      // DMA channel 4,5,6,7 and DMA2 channel 3,4,5: 26
+    Some(irq_handler),      // ADC and COMP (ADC combined with EXTI lines 21 and 22): 27
+    Some(irq_handler),      // TIM1 break, update, trigger, communication: 28
+    Some(irq_handler),      // TIM1 capture compare: 29
+    Some(irq_handler),      // TIM2 global: 30
+    Some(irq_handler),      // TIM3 global: 31
+    Some(irq_handler),      // TIM6 global and DAC underrun: 32
+    Some(irq_handler),      // TIM7 global: 33
+    Some(irq_handler),      // TIM14 global: 34
+    Some(irq_handler),      // TIM15 global: 35
+    Some(irq_handler),      // TIM16 global: 36
+    Some(irq_handler),      // TIM17 global: 37
+    Some(irq_handler),      // I2C1 global (combined with EXTI Line 23): 38
+    Some(irq_handler),      // I2C2 global: 39
+    Some(irq_handler),      // SPI1 global: 40
+    Some(irq_handler),      // SPI2 global: 41
+    Some(irq_handler),      // USART1 global (combined with EXTI Line 25): 42
+    Some(irq_handler),      // USART2 global (combined with EXTI Line 26): 43
+    Some(irq_handler),      // USART3,4,5,6,7,8 (combined with EXTI Line 28): 44
+    Some(irq_handler),      // CEC and CAN global (combined with EXTI Line 27): 45
+    Some(irq_handler),      // USB (combined with EXTI Line 18): 46
 ];
 
 
+// Dispatches every peripheral interrupt to either the handler registered with
+// `interrupt::register_handler` or the kernel's default handler for that interrupt.
+unsafe extern "C" fn irq_handler() {
+    let scb = system_control::scb();
+    // External interrupts start at exception number 16
+    let irq = scb.get_active_vector().wrapping_sub(16);
+    let hardware = match Hardware::from_irq(irq) {
+        Some(hardware) => hardware,
+        None => return default_handler(),
+    };
+
+    match interrupt::registered_handler(hardware) {
+        Some(handler) => handler(),
+        None => match hardware {
+            Hardware::Usart1 | Hardware::Usart2 | Hardware::Usart3Plus => usart_handler(hardware),
+            Hardware::Dmach4Plus => dma_chan4plus_handler(),
+            _ => default_handler(),
+        },
+    }
+}
+
 unsafe extern "C" fn default_handler() {
-    kprintln!("Unhandled Interrupt");
+    let scb = system_control::scb();
+    let vector = scb.get_active_vector();
+
+    match Hardware::from_irq(vector.wrapping_sub(16)) {
+        Some(hardware) => {
+            kprintln!("Unhandled Interrupt: {:?} (IRQ {})", hardware, hardware as u8);
+            if get_unhandled_action() == UnhandledAction::Disable {
+                let mut nvic = interrupt::nvic();
+                nvic.disable_interrupt(hardware);
+                nvic.clear_pending(hardware);
+                return;
+            }
+        },
+        None => kprintln!("Unhandled Exception: {}", vector),
+    }
     loop { ::arm::asm::bkpt() };
 }
 
+/// Non Maskable Interrupt
+///
+/// On this chip the NMI is raised by the clock security system when the HSE fails. The hardware
+/// switches the system clock over to the HSI, so we just need to acknowledge the failure and let
+/// the rest of the system know the clock has changed.
+unsafe extern "C" fn nmi_handler() {
+    let mut rcc = rcc::rcc();
+    if rcc.clock_security_failure_detected() {
+        rcc.recover_from_hse_failure();
+        kprintln!("HSE failure detected, system clock switched to the HSI");
+    }
+    else {
+        default_handler();
+    }
+}
+
 unsafe extern "C" fn hardfault_handler() {
     #[cfg(target_arch="arm")]
     {
@@ -114,11 +203,11 @@ unsafe extern "C" fn hardfault_handler() {
 /// This call enters priviledged mode to provide system call services to application code. It uses
 /// a special calling convention specific to the Altos-Rust kernel.
 ///
-/// System calls in the Altos-Rust kernel have varying numbers of arguments, when a system call is
-/// initiated, the system call number will be passed in as `r0`, all arguments to the system call
-/// will be passed in with registers starting from `r1`. So the first argument would go in `r1`,
-/// the second argument in `r2` and so on, even past the regular argument registers (`r0`-`r3`) for
-/// the `aapcs` calling convention. This is to avoid having to get arguments off of the stack.
+/// When a system call is initiated, the system call number will be passed in as `r0` and up to
+/// three arguments to the system call will be passed in `r1`-`r3`. The handler passes them on to
+/// `svc::svc_dispatch`, which looks the number up in the system call table. The result
+/// of the system call replaces the caller's stacked `r0`. If the number is outside of the table,
+/// `svc::SVC_UNKNOWN` is returned instead.
 #[naked]
 unsafe extern "C" fn sv_call_handler() {
     #[cfg(target_arch="arm")]
@@ -126,85 +215,11 @@ unsafe extern "C" fn sv_call_handler() {
     asm!(
         concat!(
             "push {r7, lr}\n", /* Save link register for return */
-            "adr r7, JUMP_TABLE\n",
-            "lsls r0, r0, #2\n",
-            "add r0, r7, r0\n",
-            "ldr r7, =JUMP_TABLE_END\n",
-            "cmp r0, r7\n", /* Make sure we're within the jump table */
-            "bhi svc_unknown\n",
-            "ldr r0, [r0]\n",
-            "mov pc, r0\n",
-
-        ".align 4\n",
-        "JUMP_TABLE:\n",
-            ".word SVC_0\n",
-            ".word SVC_1\n",
-            ".word SVC_2\n",
-            ".word SVC_3\n",
-            ".word SVC_4\n",
-            ".word SVC_5\n",
-            ".word SVC_6\n",
-            ".word SVC_7\n",
-            ".word SVC_8\n",
-            ".word SVC_9\n",
-        "JUMP_TABLE_END:\n",
-
-        "SVC_0:\n", /* exit (void) */
-            "bl sys_exit\n",
-            "b svc_end\n",
-
-        "SVC_1:\n", /* sched_yield (void) */
-            "bl sys_sched_yield\n",
-            "b svc_end\n",
-
-        "SVC_2:\n", /* sleep (wchan) */
-            "mov r0, r1\n",
-            "bl sys_sleep\n",
-            "b svc_end\n",
-
-        "SVC_3:\n", /* sleep_for (wchan, delay) */
-            "mov r0, r1\n",
-            "mov r1, r2\n",
-            "bl sys_sleep_for\n",
-            "b svc_end\n",
-
-        "SVC_4:\n", /* wake (wchan) */
-            "mov r0, r1\n",
-            "bl sys_wake\n",
-            "b svc_end\n",
-
-        "SVC_5:\n", /* mutex_lock (lock) -> bool */
-            "mov r0, r1\n",
-            "bl sys_mutex_lock\n",
-            "b svc_return\n",
-
-        "SVC_6:\n", /* mutex_try_lock (lock) -> bool */
-            "mov r0, r1\n",
-            "bl sys_mutex_try_lock\n",
-            "b svc_return\n",
-
-        "SVC_7:\n", /* mutex_unlock (lock) */
-            "mov r0, r1\n",
-            "bl sys_mutex_unlock\n",
-            "b svc_end\n",
-
-        "SVC_8:\n", /* condvar_wait (condvar, lock) */
-            "mov r0, r1\n",
-            "mov r1, r2\n",
-            "bl sys_condvar_wait\n",
-            "b svc_end\n",
-
-        "SVC_9:\n", /* condvar_broadcast (condvar, lock) */
-            "mov r0, r1\n",
-            "mov r1, r2\n",
-            "bl sys_condvar_wait\n",
-            "b svc_end\n",
-
-        "svc_return:\n", /* we have a value to return, return value in r0 */
+            "bl svc_dispatch\n", /* number and arguments are already in r0-r3 */
+
+            /* return value in r0 */
             "mrs r7, psp\n", /* assume we are called from user code */
             "str r0, [r7]\n", /* replace old r0 value with our new one */
-        "svc_unknown:\n",
-        "svc_end:\n",
             "pop {r7, pc}\n" /* restore r7 and return */
         )
         : /* no outputs */
@@ -263,6 +278,8 @@ unsafe extern "C" fn pend_sv_handler() {
             "stmia r0!, {r4-r7}\n",
 
             "push {r3, r14}\n", /* store pointer to current task and lr on main stack */
+            "mov r0, r2\n",
+            "bl check_task_stack\n", /* make sure the task didn't overflow its stack */
             "bl switch_context\n",
             "pop {r2, r3}\n", /* pointer to current task now in r2, lr goes in r3 */
 
@@ -294,20 +311,32 @@ unsafe extern "C" fn pend_sv_handler() {
     );
 }
 
-// Interrupt handler for Usart2
-unsafe extern "C" fn usart2_handler() {
+// Interrupt handler for the USARTs. USART3 to USART8 share an interrupt, so every
+// open USART on it gets serviced.
+unsafe fn usart_handler(hardware: Hardware) {
     #[cfg(feature="serial")]
     {
-        use peripheral::usart::{UsartX, Usart};
-        use self::usart::{usart_tx, usart_rx};
-        // Bits set in this register are stored in the usart2 in use, and
-        // are reflected in the address for this usart2 variable as well.
-        let usart2 = Usart::new(UsartX::Usart2);
-        usart_rx(usart2);
-        usart_tx(usart2);
+        use peripheral::usart::{self, UsartX};
+        use self::usart::{usart_tx, usart_rx, usart_cts};
+
+        let mut handled = false;
+        for &x in UsartX::all() {
+            if x.hardware() == hardware && usart::is_open(x) {
+                usart_cts(x);
+                usart_rx(x);
+                usart_tx(x);
+                handled = true;
+            }
+        }
+        if !handled {
+            default_handler();
+        }
     }
     #[cfg(not(feature="serial"))]
-    default_handler();
+    {
+        let _ = hardware;
+        default_handler();
+    }
 }
 
 // Interrupt handler for DMA Channels 4 and above.
@@ -0,0 +1,242 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module defines the table used by the supervisor call handler to dispatch system calls.
+//!
+//! A system call is made by placing its number in `r0` and its arguments in `r1`-`r3` before
+//! executing an `svc` instruction. Every system call returns a single word in `r0`, calls that
+//! have no meaningful result return `SVC_OK`. The system call numbers are defined by
+//! `altos_core::syscall`, which makes the supervisor calls, so both sides always agree.
+
+use altos_core::syscall;
+use altos_core::sync::{RawMutex, CondVar};
+use altos_core::args::Args;
+use altos_core::{TaskHandle, Priority};
+use stack;
+
+pub use altos_core::syscall::{SYS_EXIT, SYS_SCHED_YIELD, SYS_SLEEP, SYS_SLEEP_FOR, SYS_WAKE};
+pub use altos_core::syscall::{SYS_MUTEX_LOCK, SYS_MUTEX_TRY_LOCK, SYS_MUTEX_UNLOCK};
+pub use altos_core::syscall::{SYS_CONDVAR_WAIT, SYS_CONDVAR_BROADCAST};
+pub use altos_core::syscall::{SYS_NEW_TASK, SYS_TASK_PRIORITY, SYS_TASK_SET_PRIORITY};
+pub use altos_core::syscall::SYS_TASK_IS_VALID;
+
+/// The number of system calls in the dispatch table.
+pub const SYSCALL_COUNT: usize = SYS_TASK_IS_VALID + 1;
+
+/// Returned by system calls that completed successfully but have no result.
+pub const SVC_OK: usize = 0;
+/// Returned by task system calls when the task handle is no longer valid.
+pub const SVC_INVALID_TASK: usize = !0 - 1;
+/// Returned when the system call number is not in the dispatch table.
+pub const SVC_UNKNOWN: usize = !0;
+
+/// Arguments for the `SYS_NEW_TASK` system call, passed by pointer in `r1`.
+///
+/// This is the layout `altos_core::syscall::new_task` builds on its side of the `svc`, so the
+/// fields and their order are part of the system call ABI and must match it exactly.
+///
+/// The `args` are moved into the new task, and on success the handle to the task is stored in
+/// `handle`.
+#[repr(C)]
+pub struct NewTaskArgs {
+    /// The task's entry point.
+    pub code: fn(&mut Args),
+    /// The arguments passed to the task's entry point.
+    pub args: Option<Args>,
+    /// The size of the task's stack.
+    pub stack_depth: usize,
+    /// The priority of the task.
+    pub priority: Priority,
+    /// The name of the task.
+    pub name: &'static str,
+    /// Where the handle to the new task is stored.
+    pub handle: Option<TaskHandle>,
+}
+
+type Syscall = unsafe extern "C" fn(usize, usize, usize) -> usize;
+
+// The index of each entry MUST match its system call number, `svc_dispatch` indexes straight
+// into this table.
+static SVC_TABLE: [Syscall; SYSCALL_COUNT] = [
+    svc_exit,               // SYS_EXIT
+    svc_sched_yield,        // SYS_SCHED_YIELD
+    svc_sleep,              // SYS_SLEEP
+    svc_sleep_for,          // SYS_SLEEP_FOR
+    svc_wake,               // SYS_WAKE
+    svc_mutex_lock,         // SYS_MUTEX_LOCK
+    svc_mutex_try_lock,     // SYS_MUTEX_TRY_LOCK
+    svc_mutex_unlock,       // SYS_MUTEX_UNLOCK
+    svc_condvar_wait,       // SYS_CONDVAR_WAIT
+    svc_condvar_broadcast,  // SYS_CONDVAR_BROADCAST
+    svc_new_task,           // SYS_NEW_TASK
+    svc_task_priority,      // SYS_TASK_PRIORITY
+    svc_task_set_priority,  // SYS_TASK_SET_PRIORITY
+    svc_task_is_valid,      // SYS_TASK_IS_VALID
+];
+
+// Called by the supervisor call handler with the registers the caller set up. Numbers outside of
+// the table return `SVC_UNKNOWN`.
+#[doc(hidden)]
+#[no_mangle]
+pub unsafe extern "C" fn svc_dispatch(number: usize, arg1: usize, arg2: usize, arg3: usize) -> usize {
+    match SVC_TABLE.get(number) {
+        Some(syscall) => syscall(arg1, arg2, arg3),
+        None => SVC_UNKNOWN,
+    }
+}
+
+unsafe extern "C" fn svc_exit(_: usize, _: usize, _: usize) -> usize {
+    syscall::sys_exit();
+}
+
+unsafe extern "C" fn svc_sched_yield(_: usize, _: usize, _: usize) -> usize {
+    syscall::sys_sched_yield();
+    SVC_OK
+}
+
+unsafe extern "C" fn svc_sleep(wchan: usize, _: usize, _: usize) -> usize {
+    syscall::sys_sleep(wchan);
+    SVC_OK
+}
+
+unsafe extern "C" fn svc_sleep_for(wchan: usize, delay: usize, _: usize) -> usize {
+    syscall::sys_sleep_for(wchan, delay);
+    SVC_OK
+}
+
+unsafe extern "C" fn svc_wake(wchan: usize, _: usize, _: usize) -> usize {
+    syscall::sys_wake(wchan);
+    SVC_OK
+}
+
+unsafe extern "C" fn svc_mutex_lock(lock: usize, _: usize, _: usize) -> usize {
+    syscall::sys_mutex_lock(&*(lock as *const RawMutex)) as usize
+}
+
+unsafe extern "C" fn svc_mutex_try_lock(lock: usize, _: usize, _: usize) -> usize {
+    syscall::sys_mutex_try_lock(&*(lock as *const RawMutex)) as usize
+}
+
+unsafe extern "C" fn svc_mutex_unlock(lock: usize, _: usize, _: usize) -> usize {
+    syscall::sys_mutex_unlock(&*(lock as *const RawMutex));
+    SVC_OK
+}
+
+unsafe extern "C" fn svc_condvar_wait(condvar: usize, lock: usize, _: usize) -> usize {
+    syscall::sys_condvar_wait(&*(condvar as *const CondVar), &*(lock as *const RawMutex));
+    SVC_OK
+}
+
+unsafe extern "C" fn svc_condvar_broadcast(condvar: usize, _: usize, _: usize) -> usize {
+    syscall::sys_condvar_broadcast(&*(condvar as *const CondVar));
+    SVC_OK
+}
+
+unsafe extern "C" fn svc_new_task(new_task: usize, _: usize, _: usize) -> usize {
+    let new_task = &mut *(new_task as *mut NewTaskArgs);
+    let args = match new_task.args.take() {
+        Some(args) => args,
+        None => Args::empty(),
+    };
+    let handle = stack::sys_new_task(new_task.code, args, new_task.stack_depth,
+        new_task.priority, new_task.name);
+    new_task.handle = Some(handle);
+    SVC_OK
+}
+
+// The priority is written to the location pointed to by the second argument
+unsafe extern "C" fn svc_task_priority(handle: usize, priority: usize, _: usize) -> usize {
+    let handle = &*(handle as *const TaskHandle);
+    match handle.priority() {
+        Ok(current) => {
+            *(priority as *mut Priority) = current;
+            SVC_OK
+        },
+        Err(_) => SVC_INVALID_TASK,
+    }
+}
+
+// The new priority is read from the location pointed to by the second argument
+unsafe extern "C" fn svc_task_set_priority(handle: usize, priority: usize, _: usize) -> usize {
+    let handle = &mut *(handle as *mut TaskHandle);
+    match handle.set_priority(*(priority as *const Priority)) {
+        Ok(_) => SVC_OK,
+        Err(_) => SVC_INVALID_TASK,
+    }
+}
+
+unsafe extern "C" fn svc_task_is_valid(handle: usize, _: usize, _: usize) -> usize {
+    let handle = &*(handle as *const TaskHandle);
+    handle.is_valid() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_task(_args: &mut Args) {}
+
+    #[test]
+    fn test_svc_table_entries_match_syscall_numbers() {
+        let expected: [(usize, Syscall); SYSCALL_COUNT] = [
+            (SYS_EXIT, svc_exit),
+            (SYS_SCHED_YIELD, svc_sched_yield),
+            (SYS_SLEEP, svc_sleep),
+            (SYS_SLEEP_FOR, svc_sleep_for),
+            (SYS_WAKE, svc_wake),
+            (SYS_MUTEX_LOCK, svc_mutex_lock),
+            (SYS_MUTEX_TRY_LOCK, svc_mutex_try_lock),
+            (SYS_MUTEX_UNLOCK, svc_mutex_unlock),
+            (SYS_CONDVAR_WAIT, svc_condvar_wait),
+            (SYS_CONDVAR_BROADCAST, svc_condvar_broadcast),
+            (SYS_NEW_TASK, svc_new_task),
+            (SYS_TASK_PRIORITY, svc_task_priority),
+            (SYS_TASK_SET_PRIORITY, svc_task_set_priority),
+            (SYS_TASK_IS_VALID, svc_task_is_valid),
+        ];
+
+        for &(number, handler) in expected.iter() {
+            assert_eq!(SVC_TABLE[number] as usize, handler as usize);
+        }
+    }
+
+    #[test]
+    fn test_dispatch_unknown_number_is_unknown() {
+        assert_eq!(unsafe { svc_dispatch(SYSCALL_COUNT, 0, 0, 0) }, SVC_UNKNOWN);
+    }
+
+    #[test]
+    fn test_dispatch_out_of_range_number_is_bounds_checked() {
+        assert_eq!(unsafe { svc_dispatch(!0, 0, 0, 0) }, SVC_UNKNOWN);
+        assert_eq!(unsafe { svc_dispatch(!0 / 4 + 1, 0, 0, 0) }, SVC_UNKNOWN);
+    }
+
+    #[test]
+    fn test_dispatch_invalid_handle_is_invalid_task() {
+        let handle = syscall::new_task(test_task, Args::empty(), 512, Priority::Normal, "svc_test");
+        handle.destroy();
+        let handle_ptr = &handle as *const TaskHandle as usize;
+
+        let mut priority = Priority::Normal;
+        let priority_ptr = &mut priority as *mut Priority as usize;
+        assert_eq!(unsafe { svc_dispatch(SYS_TASK_PRIORITY, handle_ptr, priority_ptr, 0) },
+            SVC_INVALID_TASK);
+        assert_eq!(unsafe { svc_dispatch(SYS_TASK_SET_PRIORITY, handle_ptr, priority_ptr, 0) },
+            SVC_INVALID_TASK);
+        assert_eq!(unsafe { svc_dispatch(SYS_TASK_IS_VALID, handle_ptr, 0, 0) }, 0);
+    }
+}
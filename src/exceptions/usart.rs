@@ -16,38 +16,110 @@
 */
 extern crate arm;
 
-use peripheral::usart::{Usart, USART2_TX_CHAN, USART2_RX_CHAN};
+use peripheral::usart::{Usart, UsartX, HardwareFlowControl};
 use altos_core::syscall;
-use io::{TX_BUFFER, RX_BUFFER};
+use io::{tx_buffer, record_error, hold_byte, SerialError};
+use io::{receive_control, insert_received, take_control_byte, transmit_paused};
 
 // Handles transmitting any bytes when an interrupt is generated
-pub fn usart_tx(mut usart: Usart) {
-    if usart.is_tx_reg_empty() {
-        if let Some(byte) = unsafe { TX_BUFFER.remove() } {
+pub fn usart_tx(x: UsartX) {
+    // UNSAFE: The handler only runs for a Usart that the kernel has opened
+    let mut usart = unsafe { Usart::new(x) };
+    // Usart3 through Usart8 share an interrupt, so TXE being set doesn't mean
+    // this Usart is the one that wants to transmit.
+    if usart.is_tx_reg_empty() && usart.is_transmit_interrupt_enabled() {
+        // XON/XOFF go out ahead of anything buffered, even while the peer has paused us.
+        if let Some(byte) = unsafe { take_control_byte(x) } {
+            usart.transmit_byte(byte);
+        }
+        else if unsafe { transmit_paused(x) } {
+            // Receiving XON turns the interrupt back on.
+            usart.disable_transmit_interrupt();
+        }
+        else if let Some(byte) = unsafe { tx_buffer(x).remove() } {
             usart.transmit_byte(byte);
         }
         else {
             usart.disable_transmit_interrupt();
-            syscall::sys_wake(USART2_TX_CHAN);
+            syscall::sys_wake(x.tx_chan());
         }
     }
 
-    if usart.is_transmission_complete() {
+    // TC is left set once the line goes idle so a later flush can see there is
+    // nothing left to wait for. It's only acted on while a flush is waiting.
+    if usart.is_transmission_complete() && usart.is_transmit_complete_interrupt_enabled() {
         usart.disable_transmit_complete_interrupt();
-        syscall::sys_wake(USART2_TX_CHAN);
-        usart.clear_tc_flag();
+        syscall::sys_wake(x.tx_chan());
     }
 }
 
 /// Handles receiving any bytes when an interrupt is generated
-pub fn usart_rx(mut usart: Usart) {
-    // Clears overrun error flag.
+pub fn usart_rx(x: UsartX) {
+    // UNSAFE: The handler only runs for a Usart that the kernel has opened
+    let mut usart = unsafe { Usart::new(x) };
+    // The overrun error flag has to be cleared even when there's no byte waiting.
     // If not cleared, interrupt gets repeatedly generated
     // when it doesn't need to be, and loops infinitely.
-    usart.clear_ore_flag();
-    if usart.is_rx_reg_full() {
+    if usart.is_overrun() {
+        usart.clear_ore_flag();
+        unsafe { record_error(x, SerialError::Overrun) };
+        syscall::sys_wake(x.rx_chan());
+    }
+
+    // RXNE stays set while RTS flow control is holding the peer off, and Usart3
+    // through Usart8 share an interrupt, so only read when the interrupt is on.
+    if usart.is_rx_reg_full() && usart.is_receiver_not_empty_interrupt_enabled() {
+        let error = if usart.is_parity_error() {
+            Some(SerialError::Parity)
+        }
+        else if usart.is_framing_error() {
+            Some(SerialError::Framing)
+        }
+        else if usart.is_noise_detected() {
+            Some(SerialError::Noise)
+        }
+        else {
+            None
+        };
+
+        // Reading the byte clears RXNE, even if it's going to be discarded.
         let byte = usart.load_byte();
-        unsafe { RX_BUFFER.insert(byte) };
-        syscall::sys_wake(USART2_RX_CHAN);
+        match error {
+            Some(error) => {
+                usart.clear_pe_flag();
+                usart.clear_fe_flag();
+                usart.clear_nf_flag();
+                unsafe { record_error(x, error) };
+            },
+            None if unsafe { receive_control(x, byte) } => {},
+            None => {
+                if !unsafe { insert_received(x, byte) } {
+                    match usart.get_hardware_flow_control() {
+                        HardwareFlowControl::Rts | HardwareFlowControl::All => {
+                            // Leave the next byte in the RDR so nRTS stays deasserted
+                            // until a reader makes room.
+                            unsafe { hold_byte(x, byte) };
+                            usart.disable_receiver_not_empty_interrupt();
+                        },
+                        _ => unsafe { record_error(x, SerialError::Overrun) },
+                    }
+                }
+            },
+        }
+        syscall::sys_wake(x.rx_chan());
+    }
+}
+
+/// Handles changes on the nCTS input when CTS flow control is enabled
+pub fn usart_cts(x: UsartX) {
+    // UNSAFE: The handler only runs for a Usart that the kernel has opened
+    let mut usart = unsafe { Usart::new(x) };
+    if usart.is_cts_changed() {
+        usart.clear_cts_flag();
+        // The Usart holds the byte in the TDR while nCTS is deasserted, make sure
+        // transmission picks up again once the peer is ready.
+        if usart.is_cts_asserted() && !unsafe { tx_buffer(x).is_empty() } {
+            usart.enable_transmit_interrupt();
+        }
     }
 }
@@ -51,6 +51,8 @@ pub const NVIC_USART3PLUS_INT: isize = 29;
 pub const NVIC_CECCAN_INT: isize = 30;
 pub const NVIC_USB_INT: isize = 31;
 
+pub const NVIC_INT_COUNT: usize = 32;
+
 // IPR
 pub const IPR_PRIORITY_FIELD_MASK: u32 = 0b11 << 6;
 
@@ -39,7 +39,9 @@ impl ICER {
     pub fn disable_interrupt(&mut self, hardware: Hardware) {
         let interrupt = hardware as u8;
 
-        self.0 |= 0b1 << interrupt;
+        // Writing a 0 has no effect, so only write the bit we want to clear. Or-ing with the
+        // current value would clear every other bit that reads back as set.
+        self.0 = 0b1 << interrupt;
     }
 }
 
@@ -70,4 +72,13 @@ mod tests {
         icer.disable_interrupt(Hardware::Flash);
         assert_eq!(icer.0, 0b1 << 3);
     }
+
+    #[test]
+    fn test_icer_disable_interrupt_only_writes_one_bit() {
+        // Exti01 reads back as enabled
+        let mut icer = ICER(0b1 << 5);
+
+        icer.disable_interrupt(Hardware::Flash);
+        assert_eq!(icer.0, 0b1 << 3);
+    }
 }
@@ -0,0 +1,84 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module keeps a table of interrupt handlers registered at runtime.
+//!
+//! Every peripheral interrupt vector points to the same dispatch routine, which looks up the
+//! handler registered for the active interrupt in this table. If no handler has been registered
+//! the kernel's default handler for that interrupt is run instead.
+
+use altos_core::sync::CriticalSection;
+use super::Hardware;
+use super::defs::*;
+
+/// Signature of a function that can be registered as an interrupt handler.
+pub type Handler = fn();
+
+static mut HANDLERS: [Option<Handler>; NVIC_INT_COUNT] = [None; NVIC_INT_COUNT];
+
+/// Register a handler to be called when the interrupt for the specified peripheral fires.
+///
+/// The handler replaces the kernel's default handler for the interrupt, and any handler that was
+/// previously registered is returned. Registering a handler does not enable the interrupt, that
+/// still has to be done through the `Nvic`.
+pub fn register_handler(hardware: Hardware, handler: Handler) -> Option<Handler> {
+    let _g = CriticalSection::begin();
+    // UNSAFE: Interrupts are disabled, so no handler can be dispatched while we're modifying
+    unsafe {
+        let old = HANDLERS[hardware as usize];
+        HANDLERS[hardware as usize] = Some(handler);
+        old
+    }
+}
+
+/// Remove the handler registered for the specified peripheral, restoring the kernel's default
+/// handler. Returns the handler that was registered, if there was one.
+pub fn unregister_handler(hardware: Hardware) -> Option<Handler> {
+    let _g = CriticalSection::begin();
+    // UNSAFE: Interrupts are disabled, so no handler can be dispatched while we're modifying
+    unsafe { HANDLERS[hardware as usize].take() }
+}
+
+/// Get the handler registered for the specified peripheral, if there is one.
+pub fn registered_handler(hardware: Hardware) -> Option<Handler> {
+    // UNSAFE: Reading a single word out of the table, registration can't tear this read
+    unsafe { HANDLERS[hardware as usize] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler_a() {}
+    fn handler_b() {}
+
+    #[test]
+    fn test_register_handler_returns_previous_handler() {
+        assert!(register_handler(Hardware::Tim14, handler_a).is_none());
+        assert!(register_handler(Hardware::Tim14, handler_b).is_some());
+        assert!(registered_handler(Hardware::Tim14).is_some());
+    }
+
+    #[test]
+    fn test_unregister_handler_clears_handler() {
+        register_handler(Hardware::Tim16, handler_a);
+
+        assert!(unregister_handler(Hardware::Tim16).is_some());
+        assert!(registered_handler(Hardware::Tim16).is_none());
+        assert!(unregister_handler(Hardware::Tim16).is_none());
+    }
+}
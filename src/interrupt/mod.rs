@@ -19,6 +19,7 @@
 
 mod defs;
 mod enable;
+mod handler;
 mod pending;
 mod priority;
 
@@ -29,10 +30,11 @@ use self::priority::IPR;
 use self::defs::*;
 use core::ops::{Deref, DerefMut};
 pub use self::priority::Priority;
+pub use self::handler::{Handler, register_handler, unregister_handler, registered_handler};
 
 // Defines all the perpherials that have interrupts.
 #[allow(missing_docs)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Hardware {
     Wwdg = NVIC_WWDG_INT,
     Pvdvddio2 = NVIC_PVDVDDIO2_INT,
@@ -68,8 +70,55 @@ pub enum Hardware {
     Usb = NVIC_USB_INT,
 }
 
+impl Hardware {
+    /// Get the peripheral associated with an interrupt number, if the number is within the range
+    /// of external interrupts.
+    pub fn from_irq(irq: u8) -> Option<Hardware> {
+        let hardware = match irq as isize {
+            NVIC_WWDG_INT => Hardware::Wwdg,
+            NVIC_PVDVDDIO2_INT => Hardware::Pvdvddio2,
+            NVIC_RTC_INT => Hardware::Rtc,
+            NVIC_FLASH_INT => Hardware::Flash,
+            NVIC_RCCCRS_INT => Hardware::Rcccrs,
+            NVIC_EXTI01_INT => Hardware::Exti01,
+            NVIC_EXTI23_INT => Hardware::Exti23,
+            NVIC_EXTI415_INT => Hardware::Exti415,
+            NVIC_TSC_INT => Hardware::Tsc,
+            NVIC_DMACH1_INT => Hardware::Dmach1,
+            NVIC_DMACH23_INT => Hardware::Dmach23,
+            NVIC_DMACH4PLUS_INT => Hardware::Dmach4Plus,
+            NVIC_ADCCOMP_INT => Hardware::Adccomp,
+            NVIC_TIM1BRKUP_INT => Hardware::Tim1Brkup,
+            NVIC_TIM1CC_INT => Hardware::Tim1cc,
+            NVIC_TIM2_INT => Hardware::Tim2,
+            NVIC_TIM3_INT => Hardware::Tim3,
+            NVIC_TIM6_INT => Hardware::Tim6,
+            NVIC_TIM7_INT => Hardware::Tim7,
+            NVIC_TIM14_INT => Hardware::Tim14,
+            NVIC_TIM15_INT => Hardware::Tim15,
+            NVIC_TIM16_INT => Hardware::Tim16,
+            NVIC_TIM17_INT => Hardware::Tim17,
+            NVIC_I2C1_INT => Hardware::I2C1,
+            NVIC_I2C2_INT => Hardware::I2C2,
+            NVIC_SPI1_INT => Hardware::Spi1,
+            NVIC_SPI2_INT => Hardware::Spi2,
+            NVIC_USART1_INT => Hardware::Usart1,
+            NVIC_USART2_INT => Hardware::Usart2,
+            NVIC_USART3PLUS_INT => Hardware::Usart3Plus,
+            NVIC_CECCAN_INT => Hardware::Ceccan,
+            NVIC_USB_INT => Hardware::Usb,
+            _ => return None,
+        };
+        Some(hardware)
+    }
+}
+
 /// Get an instance of the nested vector interrupt control.
-pub fn nvic() -> Nvic {
+///
+/// This is unsafe because it creates a second owner of the NVIC, application code should get it
+/// from `Peripherals::take` instead.
+#[doc(hidden)]
+pub unsafe fn nvic() -> Nvic {
     Nvic::new()
 }
 
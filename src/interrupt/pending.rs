@@ -40,7 +40,8 @@ impl ICPR {
     pub fn clear_pending(&mut self, hardware: Hardware) {
         let interrupt = hardware as u8;
 
-        self.0 |= 0b1 << interrupt;
+        // Reads return the pending bits, so or-ing here would clear every pending interrupt
+        self.0 = 0b1 << interrupt;
     }
 }
 
@@ -71,4 +72,13 @@ mod tests {
         icpr.clear_pending(Hardware::Flash);
         assert_eq!(icpr.0, 0b1 << 3);
     }
+
+    #[test]
+    fn test_icpr_clear_pending_only_writes_one_bit() {
+        // Exti01 reads back as pending
+        let mut icpr = ICPR(0b1 << 5);
+
+        icpr.clear_pending(Hardware::Flash);
+        assert_eq!(icpr.0, 0b1 << 3);
+    }
 }
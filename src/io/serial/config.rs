@@ -0,0 +1,270 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module provides a validated runtime configuration for a serial port.
+//!
+//! A `SerialConfig` describes the baud rate and the frame format of a port. The baud rate
+//! divider is computed from the Usart's clock and checked before anything is changed, so an
+//! invalid configuration leaves the port running as it was.
+
+use peripheral::usart::{WordLength, Parity, StopLength, usart_div, actual_baud_rate};
+use peripheral::usart::defs::{USARTDIV_MIN, USARTDIV_MAX, BAUD_ERROR_MAX_PERMILLE};
+
+/// Errors that can occur when validating a serial configuration.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// The baud rate (in Hz) is zero.
+    InvalidBaudRate(u32),
+    /// The baud rate (in Hz) is too fast for the Usart's clock, even when oversampling by 8.
+    BaudRateTooFast(u32),
+    /// The baud rate (in Hz) is too slow for the Usart's clock.
+    BaudRateTooSlow(u32),
+    /// The closest baud rate the Usart's clock can produce is too far from the one requested.
+    BaudRateInaccurate {
+        /// The requested baud rate in Hz.
+        requested: u32,
+        /// The closest baud rate that can be produced in Hz.
+        actual: u32,
+    },
+}
+
+/// The divider and oversampling mode that produce a baud rate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BaudDivider {
+    /// The value of USARTDIV.
+    pub div: u32,
+    /// True if oversampling by 8 is needed, false to oversample by 16.
+    pub over8: bool,
+    /// The baud rate actually produced, in Hz.
+    pub actual: u32,
+}
+
+/// A configuration for a serial port.
+///
+/// Example Usage:
+/// ```
+///   // 250000 baud, 7 data bits, even parity, 2 stop bits
+///   let config = SerialConfig::new(250_000)
+///       .word_length(WordLength::Seven)
+///       .parity(Parity::Even)
+///       .stop_bits(StopLength::Two);
+///   serial.configure(config).expect("Invalid serial configuration!");
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct SerialConfig {
+    baud_rate: u32,
+    word_length: WordLength,
+    parity: Parity,
+    stop_bits: StopLength,
+}
+
+impl Default for SerialConfig {
+    /// 115200 baud with 8 data bits, no parity and 1 stop bit.
+    fn default() -> Self {
+        SerialConfig::new(115_200)
+    }
+}
+
+impl SerialConfig {
+    /// Run at `baud_rate` Hz with 8 data bits, no parity and 1 stop bit.
+    pub fn new(baud_rate: u32) -> Self {
+        SerialConfig {
+            baud_rate: baud_rate,
+            word_length: WordLength::Eight,
+            parity: Parity::None,
+            stop_bits: StopLength::One,
+        }
+    }
+
+    /// Set the word length. When parity is enabled the parity bit is the last bit of the word.
+    pub fn word_length(mut self, length: WordLength) -> Self {
+        self.word_length = length;
+        self
+    }
+
+    /// Set the parity.
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    /// Set the number of stop bits.
+    pub fn stop_bits(mut self, length: StopLength) -> Self {
+        self.stop_bits = length;
+        self
+    }
+
+    /// Get the requested baud rate in Hz.
+    pub fn get_baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+
+    /// Get the word length.
+    pub fn get_word_length(&self) -> WordLength {
+        self.word_length
+    }
+
+    /// Get the parity.
+    pub fn get_parity(&self) -> Parity {
+        self.parity
+    }
+
+    /// Get the number of stop bits.
+    pub fn get_stop_bits(&self) -> StopLength {
+        self.stop_bits
+    }
+
+    /// Compute the baud rate divider for a Usart driven by a `clock_rate` Hz clock.
+    ///
+    /// Both oversampling modes are tried and the one producing the closer baud rate is used.
+    /// Oversampling by 16 wins a tie since it tolerates more clock deviation. The result is
+    /// rejected if the produced baud rate is off by more than 2%.
+    pub fn baud_divider(&self, clock_rate: u32) -> Result<BaudDivider, ConfigError> {
+        let baud_rate = self.baud_rate;
+        if baud_rate == 0 {
+            return Err(ConfigError::InvalidBaudRate(baud_rate));
+        }
+
+        match (divider_for(baud_rate, clock_rate, false), divider_for(baud_rate, clock_rate, true)) {
+            (Ok((over16, error16)), Ok((over8, error8))) => {
+                if error8 < error16 { Ok(over8) } else { Ok(over16) }
+            },
+            (Ok((over16, _)), Err(_)) => Ok(over16),
+            (Err(_), Ok((over8, _))) => Ok(over8),
+            // Oversampling by 8 only gets further when the rate is too fast for oversampling by 16
+            (Err(ConfigError::BaudRateTooFast(_)), Err(error)) => Err(error),
+            (Err(error), Err(_)) => Err(error),
+        }
+    }
+}
+
+// Computes the divider for one oversampling mode, along with how far off (in Hz) the produced
+// baud rate is.
+fn divider_for(baud_rate: u32, clock_rate: u32, over8: bool)
+    -> Result<(BaudDivider, u32), ConfigError> {
+
+    let div = usart_div(baud_rate, clock_rate, over8);
+    if div < USARTDIV_MIN {
+        return Err(ConfigError::BaudRateTooFast(baud_rate));
+    }
+    if div > USARTDIV_MAX {
+        return Err(ConfigError::BaudRateTooSlow(baud_rate));
+    }
+
+    let actual = actual_baud_rate(div, clock_rate, over8);
+    let error = if actual > baud_rate { actual - baud_rate } else { baud_rate - actual };
+    if error as u64 * 1000 > BAUD_ERROR_MAX_PERMILLE as u64 * baud_rate as u64 {
+        return Err(ConfigError::BaudRateInaccurate {
+            requested: baud_rate,
+            actual: actual,
+        });
+    }
+
+    Ok((BaudDivider {
+        div: div,
+        over8: over8,
+        actual: actual,
+    }, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_115200() {
+        assert_eq!(SerialConfig::default().get_baud_rate(), 115_200);
+    }
+
+    #[test]
+    fn test_115200_at_48mhz_uses_closer_over8() {
+        // Oversampling by 16 gives 115108 baud, by 8 gives 115246 baud
+        assert_eq!(SerialConfig::new(115_200).baud_divider(48_000_000), Ok(BaudDivider {
+            div: 833,
+            over8: true,
+            actual: 115_246,
+        }));
+    }
+
+    #[test]
+    fn test_arbitrary_baud_rate() {
+        assert_eq!(SerialConfig::new(250_000).baud_divider(48_000_000), Ok(BaudDivider {
+            div: 192,
+            over8: false,
+            actual: 250_000,
+        }));
+    }
+
+    #[test]
+    fn test_fast_baud_rate_falls_back_to_over8() {
+        assert_eq!(SerialConfig::new(4_000_000).baud_divider(48_000_000), Ok(BaudDivider {
+            div: 24,
+            over8: true,
+            actual: 4_000_000,
+        }));
+    }
+
+    #[test]
+    fn test_zero_baud_rate_is_error() {
+        assert_eq!(SerialConfig::new(0).baud_divider(48_000_000),
+            Err(ConfigError::InvalidBaudRate(0)));
+    }
+
+    #[test]
+    fn test_baud_rate_too_fast_is_error() {
+        assert_eq!(SerialConfig::new(8_000_000).baud_divider(8_000_000),
+            Err(ConfigError::BaudRateTooFast(8_000_000)));
+    }
+
+    #[test]
+    fn test_baud_rate_too_slow_is_error() {
+        assert_eq!(SerialConfig::new(300).baud_divider(48_000_000),
+            Err(ConfigError::BaudRateTooSlow(300)));
+    }
+
+    #[test]
+    fn test_inaccurate_baud_rate_uses_over8() {
+        // 8 MHz / 460800 = 17.36, rounds to 17 giving 470588 baud (2.1% fast), but oversampling
+        // by 8 gives 34.72, rounding to 35 for 457143 baud (0.8% slow)
+        assert_eq!(SerialConfig::new(460_800).baud_divider(8_000_000), Ok(BaudDivider {
+            div: 35,
+            over8: true,
+            actual: 457_143,
+        }));
+    }
+
+    #[test]
+    fn test_tie_prefers_over16() {
+        // Both modes divide 48 MHz exactly
+        assert_eq!(SerialConfig::new(1_000_000).baud_divider(48_000_000), Ok(BaudDivider {
+            div: 48,
+            over8: false,
+            actual: 1_000_000,
+        }));
+    }
+
+    #[test]
+    fn test_inaccurate_baud_rate_is_error() {
+        // Too fast to oversample by 16, and 16 MHz / 970000 = 16.49 rounds to 16 giving 1000000
+        // baud (3.1% fast) when oversampling by 8
+        assert_eq!(SerialConfig::new(970_000).baud_divider(8_000_000),
+            Err(ConfigError::BaudRateInaccurate {
+                requested: 970_000,
+                actual: 1_000_000,
+            }));
+    }
+}
@@ -19,27 +19,27 @@
 use altos_core::sync::CriticalSection;
 use core::fmt::{self, Write};
 use peripheral::dma::{self, DMAChannel, DMA_TX_CHAN4PLUS};
-use peripheral::usart::{Usart, UsartX};
-use super::WRITE_LOCK;
+use peripheral::usart::{self, Usart, UsartX};
+use super::write_lock;
 
 struct DMASerial {
     usart: Usart,
+    chan: DMAChannel,
 }
 
 impl DMASerial {
-    fn new(usart: Usart) -> Self {
-        DMASerial { usart: usart }
+    fn new(usart: Usart, chan: DMAChannel) -> Self {
+        DMASerial {
+            usart: usart,
+            chan: chan,
+        }
     }
 }
 
 impl Write for DMASerial {
     fn write_str(&mut self, string: &str) -> fmt::Result {
-        use peripheral::usart::defs::*;
-
         let g = CriticalSection::begin();
-        dma::set_dma_usart_tx(DMAChannel::Four,
-                          unsafe {USART2_ADDR.offset((TDR_OFFSET/4) as isize)},
-                          string.as_bytes());
+        dma::set_dma_usart_tx(self.chan, self.usart.tdr_addr(), string.as_bytes());
 
         ::altos_core::syscall::sys_sleep(DMA_TX_CHAN4PLUS);
         drop(g);
@@ -47,11 +47,27 @@ impl Write for DMASerial {
     }
 }
 
+// The DMA channel wired to a Usart's transmit data register. Only channel 4 is serviced by the
+// DMA interrupt handler, and of the Usarts only USART2 is wired to it without remapping.
+fn tx_dma_channel(x: UsartX) -> Option<DMAChannel> {
+    match x {
+        UsartX::Usart2 => Some(DMAChannel::Four),
+        _ => None,
+    }
+}
+
 #[doc(hidden)]
 pub fn dma_write_str(s: &str) {
-    let usart2 = Usart::new(UsartX::Usart2);
-    let mut dma_serial = DMASerial::new(usart2);
+    let x = usart::console_usart();
+    let chan = match tx_dma_channel(x) {
+        Some(chan) => chan,
+        // No DMA channel serves the console, so send it through the transmit interrupt instead
+        None => return super::write_str(s),
+    };
+    // UNSAFE: The Usart is only touched while holding its write lock
+    let usart = unsafe { Usart::new(x) };
+    let mut dma_serial = DMASerial::new(usart, chan);
 
-    let _g = WRITE_LOCK.lock();
+    let _g = write_lock(x).lock();
     dma_serial.write_str(s).ok();
 }
@@ -0,0 +1,122 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module tracks receive errors for each serial port.
+//!
+//! The Usart interrupt records errors as they are detected. Each error is counted, and the most
+//! recent one is held until a reader takes it, so a read returns an error instead of silently
+//! handing back corrupted data.
+
+use peripheral::usart::UsartX;
+use peripheral::usart::defs::USART_COUNT;
+
+/// Errors that can occur while receiving on a serial port.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SerialError {
+    /// A byte was received with the wrong parity, it was discarded.
+    Parity,
+    /// A byte was received without a valid stop bit, or a break was detected. The byte was
+    /// discarded.
+    Framing,
+    /// Noise was detected while receiving a byte, it was discarded.
+    Noise,
+    /// Bytes were lost because they arrived faster than they were read, either by the Usart
+    /// or because the receive buffer was full.
+    Overrun,
+}
+
+/// The number of receive errors of each kind that have occurred on a serial port.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ErrorCounts {
+    /// Number of parity errors.
+    pub parity: usize,
+    /// Number of framing errors.
+    pub framing: usize,
+    /// Number of bytes received with noise.
+    pub noise: usize,
+    /// Number of overruns.
+    pub overrun: usize,
+}
+
+const NO_ERRORS: ErrorCounts = ErrorCounts {
+    parity: 0,
+    framing: 0,
+    noise: 0,
+    overrun: 0,
+};
+
+// These are only modified from the Usart interrupt or inside a critical section.
+static mut ERROR_COUNTS: [ErrorCounts; USART_COUNT] = [NO_ERRORS; USART_COUNT];
+static mut PENDING_ERRORS: [Option<SerialError>; USART_COUNT] = [None; USART_COUNT];
+
+impl ErrorCounts {
+    fn record(&mut self, error: SerialError) {
+        match error {
+            SerialError::Parity => self.parity += 1,
+            SerialError::Framing => self.framing += 1,
+            SerialError::Noise => self.noise += 1,
+            SerialError::Overrun => self.overrun += 1,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub unsafe fn record_error(x: UsartX, error: SerialError) {
+    ERROR_COUNTS[x.index()].record(error);
+    PENDING_ERRORS[x.index()] = Some(error);
+}
+
+// The caller must be in a critical section.
+pub unsafe fn has_pending_error(x: UsartX) -> bool {
+    PENDING_ERRORS[x.index()].is_some()
+}
+
+// The caller must be in a critical section.
+pub unsafe fn take_pending_error(x: UsartX) -> Option<SerialError> {
+    PENDING_ERRORS[x.index()].take()
+}
+
+// The caller must be in a critical section.
+pub unsafe fn error_counts(x: UsartX) -> ErrorCounts {
+    ERROR_COUNTS[x.index()]
+}
+
+// The caller must be in a critical section.
+pub unsafe fn reset_error_counts(x: UsartX) {
+    ERROR_COUNTS[x.index()] = NO_ERRORS;
+    PENDING_ERRORS[x.index()] = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_counts_record() {
+        let mut counts = NO_ERRORS;
+        counts.record(SerialError::Parity);
+        counts.record(SerialError::Framing);
+        counts.record(SerialError::Framing);
+        counts.record(SerialError::Overrun);
+        assert_eq!(counts, ErrorCounts {
+            parity: 1,
+            framing: 2,
+            noise: 0,
+            overrun: 1,
+        });
+    }
+}
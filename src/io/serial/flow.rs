@@ -0,0 +1,269 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module implements XON/XOFF software flow control for the serial ports.
+//!
+//! When enabled on a port, XOFF is sent once the receive buffer fills past a high-water mark and
+//! XON once readers have drained it below a low-water mark. XON and XOFF received from the peer
+//! are consumed rather than buffered, and pause or resume the transmit path.
+
+use peripheral::usart::{Usart, UsartX};
+use peripheral::usart::defs::USART_COUNT;
+use super::rx_buffer;
+
+/// Resume transmission (DC1).
+pub const XON: u8 = 0x11;
+/// Pause transmission (DC3).
+pub const XOFF: u8 = 0x13;
+
+// Number of buffered bytes at which XOFF is sent. This leaves a quarter of the receive buffer for
+// bytes the peer sends before it sees the XOFF.
+fn high_water(capacity: usize) -> usize {
+    capacity - capacity / 4
+}
+
+// Number of buffered bytes at which XON is sent after an XOFF.
+fn low_water(capacity: usize) -> usize {
+    capacity / 4
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct FlowState {
+    enabled: bool,
+    xoff_sent: bool,
+    tx_paused: bool,
+    pending: Option<u8>,
+}
+
+const FLOW_INIT: FlowState = FlowState {
+    enabled: false,
+    xoff_sent: false,
+    tx_paused: false,
+    pending: None,
+};
+
+// These are only modified from the Usart interrupt or inside a critical section.
+static mut FLOW: [FlowState; USART_COUNT] = [FLOW_INIT; USART_COUNT];
+
+impl FlowState {
+    fn set_enabled(&mut self, enable: bool) -> Option<u8> {
+        self.enabled = enable;
+        self.tx_paused = false;
+        if !enable && self.xoff_sent {
+            // Don't leave the peer waiting on an XON that would never come.
+            self.xoff_sent = false;
+            return self.send(XON);
+        }
+        None
+    }
+
+    // Returns true if the byte was XON or XOFF and should not be buffered.
+    fn control(&mut self, byte: u8) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match byte {
+            XOFF => self.tx_paused = true,
+            XON => self.tx_paused = false,
+            _ => return false,
+        }
+        true
+    }
+
+    // Returns the control byte to send, if any. `len` is the number of bytes now in a receive
+    // buffer that holds `capacity`.
+    fn buffered(&mut self, len: usize, capacity: usize, inserted: bool) -> Option<u8> {
+        if self.enabled && !self.xoff_sent && (!inserted || len >= high_water(capacity)) {
+            self.xoff_sent = true;
+            return self.send(XOFF);
+        }
+        None
+    }
+
+    // Returns the control byte to send, if any.
+    fn removed(&mut self, len: usize, capacity: usize) -> Option<u8> {
+        if self.enabled && self.xoff_sent && len <= low_water(capacity) {
+            self.xoff_sent = false;
+            return self.send(XON);
+        }
+        None
+    }
+
+    fn send(&mut self, byte: u8) -> Option<u8> {
+        self.pending = Some(byte);
+        self.pending
+    }
+}
+
+// Make sure the transmit interrupt runs to send a pending control byte or bytes held back while
+// transmission was paused.
+fn restart_transmit(x: UsartX) {
+    // UNSAFE: Only the transmit interrupt is enabled, the handler does the rest
+    let mut usart = unsafe { Usart::new(x) };
+    usart.enable_transmit_interrupt();
+}
+
+fn queue_control(x: UsartX, control: Option<u8>) {
+    if control.is_some() {
+        restart_transmit(x);
+    }
+}
+
+// The caller must be in a critical section.
+pub unsafe fn set_enabled(x: UsartX, enable: bool) {
+    let flow = &mut FLOW[x.index()];
+    let was_paused = flow.tx_paused;
+    let control = flow.set_enabled(enable);
+    if was_paused || control.is_some() {
+        restart_transmit(x);
+    }
+}
+
+#[doc(hidden)]
+pub unsafe fn receive_control(x: UsartX, byte: u8) -> bool {
+    let flow = &mut FLOW[x.index()];
+    let was_paused = flow.tx_paused;
+    if !flow.control(byte) {
+        return false;
+    }
+    if was_paused && !flow.tx_paused {
+        restart_transmit(x);
+    }
+    true
+}
+
+#[doc(hidden)]
+pub unsafe fn insert_received(x: UsartX, byte: u8) -> bool {
+    let buffer = rx_buffer(x);
+    let inserted = buffer.insert(byte);
+    let control = FLOW[x.index()].buffered(buffer.len(), buffer.capacity(), inserted);
+    queue_control(x, control);
+    inserted
+}
+
+// The caller must be in a critical section.
+pub unsafe fn remove_received(x: UsartX) -> Option<u8> {
+    let buffer = rx_buffer(x);
+    let byte = buffer.remove();
+    if byte.is_some() {
+        let control = FLOW[x.index()].removed(buffer.len(), buffer.capacity());
+        queue_control(x, control);
+    }
+    byte
+}
+
+#[doc(hidden)]
+pub unsafe fn take_control_byte(x: UsartX) -> Option<u8> {
+    FLOW[x.index()].pending.take()
+}
+
+#[doc(hidden)]
+pub unsafe fn transmit_paused(x: UsartX) -> bool {
+    FLOW[x.index()].tx_paused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: usize = 32;
+
+    fn enabled() -> FlowState {
+        let mut flow = FLOW_INIT;
+        flow.set_enabled(true);
+        flow
+    }
+
+    #[test]
+    fn test_control_bytes_ignored_when_disabled() {
+        let mut flow = FLOW_INIT;
+        assert!(!flow.control(XOFF));
+        assert!(!flow.tx_paused);
+    }
+
+    #[test]
+    fn test_xoff_pauses_and_xon_resumes() {
+        let mut flow = enabled();
+        assert!(flow.control(XOFF));
+        assert!(flow.tx_paused);
+        assert!(flow.control(XON));
+        assert!(!flow.tx_paused);
+        assert!(!flow.control(b'a'));
+    }
+
+    #[test]
+    fn test_xoff_sent_at_high_water() {
+        let mut flow = enabled();
+        let high = high_water(CAPACITY);
+        for len in 1..high {
+            assert_eq!(flow.buffered(len, CAPACITY, true), None);
+        }
+        assert_eq!(flow.buffered(high, CAPACITY, true), Some(XOFF));
+        // Only sent once
+        assert_eq!(flow.buffered(high + 1, CAPACITY, true), None);
+    }
+
+    #[test]
+    fn test_xoff_sent_when_buffer_full() {
+        let mut flow = enabled();
+        assert_eq!(flow.buffered(CAPACITY, CAPACITY, false), Some(XOFF));
+    }
+
+    #[test]
+    fn test_xon_sent_at_low_water() {
+        let mut flow = enabled();
+        let (high, low) = (high_water(CAPACITY), low_water(CAPACITY));
+        flow.buffered(high, CAPACITY, true);
+        flow.pending = None;
+        for len in (low + 1..high).rev() {
+            assert_eq!(flow.removed(len, CAPACITY), None);
+        }
+        assert_eq!(flow.removed(low, CAPACITY), Some(XON));
+        assert_eq!(flow.removed(low - 1, CAPACITY), None);
+    }
+
+    #[test]
+    fn test_nothing_sent_when_disabled() {
+        let mut flow = FLOW_INIT;
+        for len in 1..CAPACITY + 1 {
+            assert_eq!(flow.buffered(len, CAPACITY, true), None);
+        }
+        assert_eq!(flow.buffered(CAPACITY, CAPACITY, false), None);
+    }
+
+    #[test]
+    fn test_disabling_after_xoff_sends_xon() {
+        let mut flow = enabled();
+        flow.buffered(CAPACITY, CAPACITY, false);
+        assert_eq!(flow.set_enabled(false), Some(XON));
+        assert!(!flow.xoff_sent);
+    }
+
+    #[test]
+    fn test_water_marks_follow_capacity() {
+        assert_eq!((high_water(32), low_water(32)), (24, 8));
+        assert_eq!((high_water(128), low_water(128)), (96, 32));
+    }
+
+    #[test]
+    fn test_set_enabled_resumes_transmission() {
+        let mut flow = enabled();
+        flow.control(XOFF);
+        flow.set_enabled(false);
+        assert!(!flow.tx_paused);
+    }
+}
@@ -17,26 +17,50 @@
 
 #[cfg(feature="dma")]
 mod dma;
+mod config;
+mod error;
+mod flow;
 
 use altos_core::volatile::Volatile;
-use altos_core::syscall::sleep;
+use altos_core::syscall::{sleep, sleep_for};
 use altos_core::sync::{Mutex, CriticalSection};
 use altos_core::collections::RingBuffer;
 use core::fmt::{self, Write, Arguments};
-use peripheral::usart::{UsartX, Usart, USART2_TX_CHAN, USART2_RX_CHAN};
+use peripheral::usart::{self, Usart, UsartX, UsartId, UsartError, BaudRate, HardwareFlowControl};
+use peripheral::usart::defs::USART_COUNT;
+use peripheral::gpio::af::{UsartTx, UsartRx, UsartCts, UsartRts};
+use peripheral::rcc;
+use time::{self, Time};
 
 #[cfg(feature="dma")]
 pub use self::dma::*;
+pub use self::config::{SerialConfig, ConfigError, BaudDivider};
+pub use self::error::{SerialError, ErrorCounts, record_error};
+pub use self::flow::{XON, XOFF, receive_control, insert_received, take_control_byte, transmit_paused};
 
-/// A buffer for transmitting bytes.
-pub static mut TX_BUFFER: RingBuffer = RingBuffer::new();
+// An array holding `$init` for each Usart the chip has.
+#[cfg(not(feature="stm32f09x"))]
+macro_rules! per_usart {
+    ($init:expr) => { [$init, $init] }
+}
+#[cfg(feature="stm32f09x")]
+macro_rules! per_usart {
+    ($init:expr) => { [$init, $init, $init, $init, $init, $init, $init, $init] }
+}
+
+/// Buffers for transmitting bytes, one for each Usart indexed by `UsartX::index`.
+pub static mut TX_BUFFERS: [RingBuffer; USART_COUNT] = per_usart!(RingBuffer::new());
+
+/// Buffers for receiving bytes, one for each Usart indexed by `UsartX::index`.
+pub static mut RX_BUFFERS: [RingBuffer; USART_COUNT] = per_usart!(RingBuffer::new());
 
-/// A buffer for receiving bytes.
-pub static mut RX_BUFFER: RingBuffer = RingBuffer::new();
+// A byte taken from each Usart while its receive buffer was full, held until a reader makes room.
+// Only used with RTS flow control, where the Usart stops being read so the peer stops sending.
+static mut HELD_BYTES: [Option<u8>; USART_COUNT] = [None; USART_COUNT];
 
-// Mutex to ensure transmitted data is not jumbled.
-static WRITE_LOCK: Mutex<()> = Mutex::new(());
-static READ_LOCK: Mutex<()> = Mutex::new(());
+// Mutexes to ensure transmitted data is not jumbled, one for each Usart.
+static WRITE_LOCKS: [Mutex<()>; USART_COUNT] = per_usart!(Mutex::new(()));
+static READ_LOCKS: [Mutex<()>; USART_COUNT] = per_usart!(Mutex::new(()));
 
 /// Print a formatted string to the serial port. This macro is intended for
 /// user code and should not be used to print within the kernel code.
@@ -58,38 +82,315 @@ macro_rules! println {
     ($fmt:expr, $($arg:tt)*) => (print!(concat!($fmt, "\n"), $($arg)*));
 }
 
-struct Serial {
+/// A serial port on one of the Usart peripherals.
+///
+/// Each Usart has its own transmit and receive buffers, wait channels and locks, so
+/// ports can be used independently of each other and of the console.
+pub struct Serial {
+    x: UsartX,
     usart: Usart,
 }
 
 impl Serial {
-    fn new(usart: Usart) -> Self {
-        Serial { usart: usart }
+    /// Get a handle to the serial port on a Usart.
+    ///
+    /// The Usart should already have been opened, either with `open` or by the kernel
+    /// for the console.
+    pub fn new(x: UsartX) -> Self {
+        Serial {
+            x: x,
+            // UNSAFE: The Usart is only touched through its locks and the interrupt handlers
+            usart: unsafe { Usart::new(x) },
+        }
+    }
+
+    /// Get a handle to the serial port the console runs on.
+    pub fn console() -> Self {
+        Serial::new(usart::console_usart())
+    }
+
+    /// The Usart this port runs on.
+    pub fn usart(&self) -> UsartX {
+        self.x
+    }
+
+    /// Change the baud rate and frame format of the port.
+    ///
+    /// Any bytes already written are sent with the old configuration first, then the Usart is
+    /// disabled while it is reconfigured. If the configuration is invalid the port is left
+    /// unchanged. Returns the baud rate actually produced, which may differ slightly from the
+    /// one requested.
+    pub fn configure(&mut self, config: SerialConfig) -> Result<u32, ConfigError> {
+        // UNSAFE: Only reading the clock configuration
+        let clock_rate = rcc::peripheral_clock_rate(unsafe { &rcc::rcc() }, self.x.peripheral());
+        let divider = try!(config.baud_divider(clock_rate));
+
+        let _g = write_lock(self.x).lock();
+        self.flush();
+
+        self.usart.disable_usart();
+        self.usart.set_word_length(config.get_word_length());
+        self.usart.set_parity(config.get_parity());
+        self.usart.set_stop_bits(config.get_stop_bits());
+        if divider.over8 {
+            self.usart.enable_over8();
+        }
+        else {
+            self.usart.disable_over8();
+        }
+        self.usart.set_baud_divider(divider.div);
+        self.usart.enable_usart();
+
+        Ok(divider.actual)
+    }
+
+    /// Turn on RTS hardware flow control, with nRTS on the given pin.
+    ///
+    /// With RTS on, the port stops reading from the Usart when its receive buffer is full, which
+    /// holds nRTS deasserted until a reader makes room, so bytes are never dropped.
+    ///
+    /// # Panics
+    ///
+    /// The pin must carry nRTS for the Usart this port runs on, or the kernel will panic.
+    pub fn enable_rts<U: UsartId, RTS: UsartRts<U>>(&mut self, mut rts: RTS) {
+        if U::usart() != self.x {
+            panic!("Serial::enable_rts - the pin carries nRTS for a different Usart!");
+        }
+        usart::connect_pin(&mut rts);
+        let cts = self.cts_enabled();
+        self.set_hardware_flow_control(true, cts);
+    }
+
+    /// Turn on CTS hardware flow control, with nCTS on the given pin.
+    ///
+    /// With CTS on, bytes are only sent while the peer asserts nCTS.
+    ///
+    /// # Panics
+    ///
+    /// The pin must carry nCTS for the Usart this port runs on, or the kernel will panic.
+    pub fn enable_cts<U: UsartId, CTS: UsartCts<U>>(&mut self, mut cts: CTS) {
+        if U::usart() != self.x {
+            panic!("Serial::enable_cts - the pin carries nCTS for a different Usart!");
+        }
+        usart::connect_pin(&mut cts);
+        let rts = self.rts_enabled();
+        self.set_hardware_flow_control(rts, true);
+    }
+
+    /// Turn RTS/CTS hardware flow control off.
+    pub fn disable_hardware_flow_control(&mut self) {
+        self.set_hardware_flow_control(false, false);
+    }
+
+    fn rts_enabled(&self) -> bool {
+        match self.usart.get_hardware_flow_control() {
+            HardwareFlowControl::Rts | HardwareFlowControl::All => true,
+            _ => false,
+        }
+    }
+
+    fn cts_enabled(&self) -> bool {
+        match self.usart.get_hardware_flow_control() {
+            HardwareFlowControl::Cts | HardwareFlowControl::All => true,
+            _ => false,
+        }
+    }
+
+    fn set_hardware_flow_control(&mut self, rts: bool, cts: bool) {
+        let hfc = match (rts, cts) {
+            (false, false) => HardwareFlowControl::None,
+            (true, false) => HardwareFlowControl::Rts,
+            (false, true) => HardwareFlowControl::Cts,
+            (true, true) => HardwareFlowControl::All,
+        };
+
+        let _g = write_lock(self.x).lock();
+        self.flush();
+
+        // RTSE and CTSE can only be written while the Usart is disabled.
+        self.usart.disable_usart();
+        self.usart.set_hardware_flow_control(hfc);
+        if cts {
+            self.usart.enable_cts_interrupt();
+        }
+        else {
+            self.usart.disable_cts_interrupt();
+        }
+        self.usart.enable_usart();
+    }
+
+    /// Turn XON/XOFF software flow control on or off.
+    ///
+    /// While on, XOFF is sent when the receive buffer is nearly full and XON once it has been
+    /// drained, and XON/XOFF received from the peer pause and resume transmission instead of
+    /// being read. Turning it off while the peer is paused sends XON.
+    pub fn set_software_flow_control(&mut self, enable: bool) {
+        let _g = CriticalSection::begin();
+        // UNSAFE: Accessing mutable static
+        unsafe { flow::set_enabled(self.x, enable) };
+    }
+
+    // Wait for every buffered byte to be shifted out of the Usart.
+    fn flush(&mut self) {
+        loop {
+            let _g = CriticalSection::begin();
+            // UNSAFE: Accessing mutable static
+            if unsafe { tx_buffer(self.x).is_empty() } {
+                break;
+            }
+            self.usart.enable_transmit_interrupt();
+            ::altos_core::syscall::sys_sleep(self.x.tx_chan());
+        }
+        // TC stays set while the line is idle, so this only sleeps when a byte
+        // is still being shifted out.
+        loop {
+            let _g = CriticalSection::begin();
+            if self.usart.is_transmission_complete() {
+                break;
+            }
+            self.usart.enable_transmit_complete_interrupt();
+            ::altos_core::syscall::sys_sleep(self.x.tx_chan());
+        }
     }
 
     fn buffer_byte(&mut self, byte: u8) {
         unsafe {
-            while !TX_BUFFER.insert(byte) {
+            while !tx_buffer(self.x).insert(byte) {
                 let _g = CriticalSection::begin();
                 self.usart.enable_transmit_interrupt();
-                ::altos_core::syscall::sys_sleep(USART2_TX_CHAN);
+                ::altos_core::syscall::sys_sleep(self.x.tx_chan());
             }
         }
     }
 
-    fn read(&mut self, buf: &mut [u8]) -> usize {
+    /// Read bytes from the port into `buf`, returning the number of bytes read.
+    ///
+    /// Blocks until at least one byte is available or a receive error has occurred. If an error
+    /// occurred since the last read it is returned instead, and any bytes received before it are
+    /// left for the next read.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, SerialError> {
+        let _g = READ_LOCKS[self.x.index()].lock();
+        self.wait_for_data(None);
+        try!(self.take_error());
+        Ok(self.read_buffered(buf))
+    }
+
+    /// Read the bytes that have already been received into `buf`, returning the number of bytes
+    /// read. Returns immediately, with `Ok(0)` if nothing has been received.
+    ///
+    /// This doesn't wait for the read lock, so bytes may be interleaved with a blocking read
+    /// happening at the same time on another task.
+    pub fn try_read(&mut self, buf: &mut [u8]) -> Result<usize, SerialError> {
+        try!(self.take_error());
+        Ok(self.read_buffered(buf))
+    }
+
+    /// Read bytes from the port into `buf`, returning the number of bytes read.
+    ///
+    /// Like `read`, but gives up after `ms` milliseconds and returns `Ok(0)` if nothing was
+    /// received.
+    pub fn read_timeout(&mut self, buf: &mut [u8], ms: usize) -> Result<usize, SerialError> {
+        let _g = READ_LOCKS[self.x.index()].lock();
+        if !self.wait_for_data(Some(ms)) {
+            return Ok(0);
+        }
+        try!(self.take_error());
+        Ok(self.read_buffered(buf))
+    }
+
+    /// Fill `buf` completely, blocking until enough bytes have been received.
+    ///
+    /// If a receive error occurs it is returned, and the bytes read up to that point are
+    /// discarded.
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), SerialError> {
+        let _g = READ_LOCKS[self.x.index()].lock();
+        let mut read = 0;
+        while read < buf.len() {
+            self.wait_for_data(None);
+            try!(self.take_error());
+            read += self.read_buffered(&mut buf[read..]);
+        }
+        Ok(())
+    }
+
+    /// Read bytes into `buf` until `delimiter` is received or `buf` is full, returning the number
+    /// of bytes read. The delimiter is included in the bytes read.
+    ///
+    /// Useful for line oriented protocols, e.g. `serial.read_until(b'\n', &mut line)`. If a
+    /// receive error occurs it is returned, and the bytes read up to that point are discarded.
+    pub fn read_until(&mut self, delimiter: u8, buf: &mut [u8]) -> Result<usize, SerialError> {
+        let _g = READ_LOCKS[self.x.index()].lock();
+        let mut read = 0;
+        while read < buf.len() {
+            self.wait_for_data(None);
+            try!(self.take_error());
+            // Only take one byte at a time so nothing past the delimiter is consumed.
+            if self.read_buffered(&mut buf[read..read + 1]) == 0 {
+                continue;
+            }
+            read += 1;
+            if buf[read - 1] == delimiter {
+                break;
+            }
+        }
+        Ok(read)
+    }
+
+    // Block until a byte has been received or a receive error has occurred. If `timeout_ms` is
+    // given, give up after that many milliseconds. Returns false if it timed out.
+    fn wait_for_data(&mut self, timeout_ms: Option<usize>) -> bool {
+        let start = time::now();
         // UNSAFE: Accessing mutable static
         while unsafe {
             let _g = CriticalSection::begin();
-            Volatile::new(&RX_BUFFER).is_empty()
+            Volatile::new(rx_buffer(self.x)).is_empty() && !error::has_pending_error(self.x)
         } {
-            sleep(USART2_RX_CHAN);
+            match timeout_ms {
+                Some(ms) => {
+                    let elapsed = ms_since(start);
+                    if elapsed >= ms {
+                        return false;
+                    }
+                    sleep_for(self.x.rx_chan(), (ms - elapsed) * time::get_resolution());
+                },
+                None => sleep(self.x.rx_chan()),
+            }
         }
+        true
+    }
+
+    // Return the receive error that occurred since the last read, if any.
+    fn take_error(&mut self) -> Result<(), SerialError> {
+        let _g = CriticalSection::begin();
+        // UNSAFE: Accessing mutable static
+        match unsafe { error::take_pending_error(self.x) } {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the number of receive errors of each kind that have occurred on the port.
+    pub fn error_counts(&self) -> ErrorCounts {
+        let _g = CriticalSection::begin();
+        // UNSAFE: Accessing mutable static
+        unsafe { error::error_counts(self.x) }
+    }
+
+    /// Reset the receive error counts for the port to zero, and discard any error a reader has
+    /// not seen yet.
+    pub fn reset_error_counts(&mut self) {
+        let _g = CriticalSection::begin();
+        // UNSAFE: Accessing mutable static
+        unsafe { error::reset_error_counts(self.x) };
+    }
+
+    // Move bytes already in the receive buffer into `buf` without blocking.
+    fn read_buffered(&mut self, buf: &mut [u8]) -> usize {
         let mut read = 0;
         while read < buf.len() {
             let g = CriticalSection::begin();
             // UNSAFE: Accessing mutable static
-            let byte = unsafe { RX_BUFFER.remove() };
+            let byte = unsafe { flow::remove_received(self.x) };
             drop(g);
             match byte {
                 Some(byte) => {
@@ -99,8 +400,26 @@ impl Serial {
                 None => break,
             }
         }
+        if read > 0 {
+            self.release_held_byte();
+        }
         read
     }
+
+    // Move a byte held back by RTS flow control into the receive buffer now that there's room,
+    // and start reading from the Usart again.
+    fn release_held_byte(&mut self) {
+        let _g = CriticalSection::begin();
+        // UNSAFE: Accessing mutable static
+        unsafe {
+            if let Some(byte) = HELD_BYTES[self.x.index()] {
+                if flow::insert_received(self.x, byte) {
+                    HELD_BYTES[self.x.index()] = None;
+                    self.usart.enable_receiver_not_empty_interrupt();
+                }
+            }
+        }
+    }
 }
 
 impl Write for Serial {
@@ -119,12 +438,54 @@ impl Write for Serial {
         }
         let g = CriticalSection::begin();
         self.usart.enable_transmit_interrupt();
-        ::altos_core::syscall::sys_sleep(USART2_TX_CHAN);
+        ::altos_core::syscall::sys_sleep(self.x.tx_chan());
         drop(g);
         Ok(())
     }
 }
 
+/// Open a serial port on the Usart `U`.
+///
+/// Sets up the TX and RX pins and configures the Usart to the given baud rate with 8N1, see
+/// `usart::open`. Fails if the Usart is already open.
+pub fn open<U, TX, RX>(tx: TX, rx: RX, baud_rate: BaudRate) -> Result<Serial, UsartError>
+    where U: UsartId, TX: UsartTx<U>, RX: UsartRx<U> {
+
+    let usart = try!(usart::open::<U, TX, RX>(tx, rx, baud_rate));
+    Ok(Serial {
+        x: U::usart(),
+        usart: usart,
+    })
+}
+
+// Milliseconds passed since `start`.
+fn ms_since(start: Time) -> usize {
+    let now = time::now();
+    let now_ms = now.sec * 1000 + now.msec;
+    let start_ms = start.sec * 1000 + start.msec;
+    now_ms.wrapping_sub(start_ms)
+}
+
+#[doc(hidden)]
+pub unsafe fn tx_buffer(x: UsartX) -> &'static mut RingBuffer {
+    &mut TX_BUFFERS[x.index()]
+}
+
+#[doc(hidden)]
+pub unsafe fn rx_buffer(x: UsartX) -> &'static mut RingBuffer {
+    &mut RX_BUFFERS[x.index()]
+}
+
+#[doc(hidden)]
+pub unsafe fn hold_byte(x: UsartX, byte: u8) {
+    HELD_BYTES[x.index()] = Some(byte);
+}
+
+#[doc(hidden)]
+pub fn write_lock(x: UsartX) -> &'static Mutex<()> {
+    &WRITE_LOCKS[x.index()]
+}
+
 struct DebugSerial {
     usart: Usart,
 }
@@ -156,19 +517,17 @@ impl Write for DebugSerial {
 
 #[doc(hidden)]
 pub fn write_fmt(args: Arguments) {
-    let usart2 = Usart::new(UsartX::Usart2);
-    let mut serial = Serial::new(usart2);
+    let mut serial = Serial::console();
 
-    let _g = WRITE_LOCK.lock();
+    let _g = write_lock(serial.x).lock();
     serial.write_fmt(args).ok();
 }
 
 #[doc(hidden)]
 pub fn write_str(s: &str) {
-    let usart2 = Usart::new(UsartX::Usart2);
-    let mut serial = Serial::new(usart2);
+    let mut serial = Serial::console();
 
-    let _g = WRITE_LOCK.lock();
+    let _g = write_lock(serial.x).lock();
     serial.write_str(s).ok();
 }
 
@@ -176,8 +535,8 @@ pub fn write_str(s: &str) {
 #[no_mangle]
 #[doc(hidden)]
 pub fn debug_fmt(args: Arguments) {
-    let usart2 = Usart::new(UsartX::Usart2);
-    let mut serial = DebugSerial::new(usart2);
+    // UNSAFE: Interrupts are off, so nothing else is using the console
+    let mut serial = DebugSerial::new(unsafe { usart::console() });
 
     serial.write_fmt(args).ok();
 }
@@ -186,20 +545,35 @@ pub fn debug_fmt(args: Arguments) {
 #[no_mangle]
 #[doc(hidden)]
 pub fn debug_str(s: &str) {
-    let usart2 = Usart::new(UsartX::Usart2);
-    let mut serial = DebugSerial::new(usart2);
+    // UNSAFE: Interrupts are off, so nothing else is using the console
+    let mut serial = DebugSerial::new(unsafe { usart::console() });
 
     serial.write_str(s).ok();
 }
 
+/// Read a character from the console, blocking until one is received.
+///
+/// Returns `None` if a receive error occurred instead.
 #[doc(hidden)]
-pub fn poll_char() -> Option<u8> {
-    let usart2 = Usart::new(UsartX::Usart2);
-    let mut serial = Serial::new(usart2);
+pub fn read_char() -> Option<u8> {
+    let mut serial = Serial::console();
     let mut buf: [u8; 1] = [0];
-    let _g = READ_LOCK.lock();
     match serial.read(&mut buf) {
-        0 => None,
-        _ => Some(buf[0]),
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(buf[0]),
+    }
+}
+
+/// Check for a character from the console without blocking.
+///
+/// Returns `None` if nothing has been received, or if a receive error occurred. Use `read_char`
+/// to wait for a character.
+#[doc(hidden)]
+pub fn poll_char() -> Option<u8> {
+    let mut serial = Serial::console();
+    let mut buf: [u8; 1] = [0];
+    match serial.try_read(&mut buf) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(buf[0]),
     }
 }
@@ -48,8 +48,10 @@ pub mod exceptions;
 pub mod interrupt;
 pub mod system_control;
 pub mod time;
+pub mod panic;
+pub mod stack;
+pub mod board;
 
-use peripheral::gpio;
 use peripheral::rcc;
 use peripheral::systick;
 
@@ -68,6 +70,7 @@ pub mod kernel {
         pub use altos_core::TaskHandle;
         pub use altos_core::{start_scheduler};
         pub use altos_core::{Priority};
+        pub use stack::{new_task, task_usage, current_task_usage, StackUsage};
     }
     /// Allocation interface to allow dynamic allocation.
     pub mod alloc {
@@ -90,14 +93,7 @@ pub mod kernel {
 #[cfg(not(any(test, feature="doc", not(target_arch="arm"))))]
 #[lang = "panic_fmt"]
 extern "C" fn panic_fmt(fmt: core::fmt::Arguments, (file, line): (&'static str, u32)) -> ! {
-    unsafe { arm::asm::disable_interrupts() };
-    kprintln!("Panicked at File: {}, Line: {}", file, line);
-    kprintln!("{}", fmt);
-    loop {
-        unsafe {
-            arm::asm::bkpt();
-        }
-    }
+    panic::begin_panic(fmt, file, line)
 }
 
 extern "Rust" {
@@ -113,7 +109,7 @@ pub fn init() -> ! {
     init_data_segment();
     init_bss_segment();
     init_heap();
-    init_led();
+    init_board();
     init_clock();
     init_ticks();
     init_usart();
@@ -187,40 +183,23 @@ fn init_heap() {
             : "r0", "r1", "r2"
             : "volatile"
         );
+        // Tasks created before any memory is freed get their stacks from freshly painted memory
+        stack::paint(heap_start, heap_size);
         altos_core::init::init_heap(heap_start, heap_size);
     }
 }
 
-fn init_led() {
-    gpio::GPIO::enable(gpio::Group::B);
-
-    let mut pb3 = gpio::Port::new(3, gpio::Group::B);
-    pb3.set_mode(gpio::Mode::Output);
-    pb3.set_type(gpio::Type::PushPull);
+fn init_board() {
+    board::init(board::board());
 }
 
 fn init_clock() {
-    let mut rcc = rcc::rcc();
-
-    // 12 is the max we can go since our input clock is (8MHz / 2)
-    let clock_multiplier: u8 = 12;
-
-    // PLL must be off in order to configure
-    rcc.disable_clock(rcc::Clock::PLL);
-
-    // Make sure HSI is the PLL source clock
-    rcc.set_pll_source(rcc::Clock::HSI);
-
-    // Set the multiplier... DO NOT EXCEED 48 MHz
-    rcc.set_pll_multiplier(clock_multiplier);
-
-    // Enable the PLL clock
-    rcc.enable_clock(rcc::Clock::PLL);
-
-    // Wait for it to be ready
-    while !rcc.clock_is_ready(rcc::Clock::PLL) {}
-    // Switch over to the PLL for running the system
-    rcc.set_system_clock_source(rcc::Clock::PLL);
+    // Run at 48 MHz off of the HSI until the application chooses its own configuration
+    // UNSAFE: The application hasn't been given the RCC yet
+    let mut rcc = unsafe { rcc::rcc() };
+    if let Err(err) = rcc::ClockConfig::default().apply(&mut rcc) {
+        panic!("init_clock - default clock configuration is invalid: {:?}", err);
+    }
 
     // Our system clock sets itself to interrupt every 1 ms
     time::set_resolution(1);
@@ -238,7 +217,7 @@ fn init_ticks() {
 
 fn init_usart() {
     #[cfg(feature="serial")]
-    peripheral::usart::init();
+    peripheral::usart::init(board::board());
 }
 
 fn init_dma() {
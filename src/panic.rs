@@ -0,0 +1,163 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module defines how the system behaves when the kernel or application panics.
+//!
+//! By default a panic halts the processor on a breakpoint so a debugger can inspect the state of
+//! the system. Production builds will usually want to reset the system instead, which can be
+//! selected with the `panic_reset` feature or at runtime with `set_strategy`.
+//!
+//! An application can also register a hook with `set_hook` that gets called before the strategy
+//! is carried out, for example to put any actuators into a safe state.
+
+use core::fmt::Arguments;
+use altos_core::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use altos_core::sync::CriticalSection;
+use system_control;
+
+/// The action taken once a panic has been reported.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Strategy {
+    /// Disable interrupts and loop on a breakpoint instruction.
+    Halt,
+    /// Request a system reset from the System Control Block.
+    Reset,
+}
+
+/// Information about a panic, passed to the registered panic hook.
+pub struct PanicInfo<'a> {
+    /// The formatted panic message.
+    pub message: Arguments<'a>,
+    /// The file the panic originated from.
+    pub file: &'static str,
+    /// The line the panic originated from.
+    pub line: u32,
+}
+
+/// Signature of a function that can be registered as a panic hook.
+pub type Hook = fn(&PanicInfo);
+
+// Zero is used to represent an unset strategy, in which case we fall back to the one chosen by
+// the cargo features.
+const STRATEGY_DEFAULT: usize = 0;
+const STRATEGY_HALT: usize = 1;
+const STRATEGY_RESET: usize = 2;
+
+static STRATEGY: AtomicUsize = ATOMIC_USIZE_INIT;
+static mut HOOK: Option<Hook> = None;
+
+/// Set the action taken when the system panics, overriding the default.
+pub fn set_strategy(strategy: Strategy) {
+    let value = match strategy {
+        Strategy::Halt => STRATEGY_HALT,
+        Strategy::Reset => STRATEGY_RESET,
+    };
+    STRATEGY.store(value, Ordering::Relaxed);
+}
+
+/// Get the action that will be taken when the system panics.
+pub fn get_strategy() -> Strategy {
+    match STRATEGY.load(Ordering::Relaxed) {
+        STRATEGY_HALT => Strategy::Halt,
+        STRATEGY_RESET => Strategy::Reset,
+        _ => default_strategy(),
+    }
+}
+
+/// Register a hook to be called when the system panics.
+///
+/// The hook is called with interrupts disabled before the panic message is printed, so it can put
+/// the system into a safe state even if printing blocks or faults. The panic strategy is carried
+/// out after the message has been printed. Any previously registered hook is replaced.
+pub fn set_hook(hook: Hook) {
+    let _g = CriticalSection::begin();
+    // UNSAFE: Interrupts are disabled, so nothing else can be accessing the hook
+    unsafe { HOOK = Some(hook) };
+}
+
+/// Remove the registered panic hook, returning it if there was one.
+pub fn take_hook() -> Option<Hook> {
+    let _g = CriticalSection::begin();
+    // UNSAFE: Interrupts are disabled, so nothing else can be accessing the hook
+    unsafe { HOOK.take() }
+}
+
+#[cfg(not(feature="panic_reset"))]
+fn default_strategy() -> Strategy {
+    Strategy::Halt
+}
+
+#[cfg(feature="panic_reset")]
+fn default_strategy() -> Strategy {
+    Strategy::Reset
+}
+
+// This should only get called by the panic_fmt lang item
+#[doc(hidden)]
+pub fn begin_panic(message: Arguments, file: &'static str, line: u32) -> ! {
+    unsafe { ::arm::asm::disable_interrupts() };
+
+    // Run the hook first, printing over serial could block or fault and the hook may need to put
+    // actuators into a safe state.
+    // UNSAFE: Interrupts are disabled, so nothing else can be accessing the hook
+    if let Some(hook) = unsafe { HOOK } {
+        let info = PanicInfo {
+            message: message,
+            file: file,
+            line: line,
+        };
+        hook(&info);
+    }
+
+    kprintln!("Panicked at File: {}, Line: {}", file, line);
+    kprintln!("{}", message);
+
+    match get_strategy() {
+        Strategy::Halt => halt(),
+        Strategy::Reset => reset(),
+    }
+}
+
+fn halt() -> ! {
+    loop {
+        unsafe {
+            ::arm::asm::bkpt();
+        }
+    }
+}
+
+fn reset() -> ! {
+    let mut scb = system_control::scb();
+    scb.request_system_reset();
+    // Make sure the reset request has gone through before we start spinning
+    unsafe { ::arm::asm::dsb() };
+    loop {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_strategy_overrides_default() {
+        set_strategy(Strategy::Reset);
+        assert_eq!(get_strategy(), Strategy::Reset);
+
+        set_strategy(Strategy::Halt);
+        assert_eq!(get_strategy(), Strategy::Halt);
+    }
+}
@@ -0,0 +1,294 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module handles the CR and CFGR registers, which control the trimming of the HSI48 and
+//! the signal it is synchronized against.
+
+use super::super::Field;
+use super::defs::*;
+
+/// The signal the HSI48 is synchronized against.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SyncSource {
+    /// The CRS_SYNC pin
+    Gpio,
+    /// The Low Speed External clock
+    Lse,
+    /// The USB start of frame packets
+    UsbSof,
+}
+
+impl Field for SyncSource {
+    fn mask(&self) -> u32 {
+        match *self {
+            SyncSource::Gpio => CFGR_SYNCSRC_GPIO,
+            SyncSource::Lse => CFGR_SYNCSRC_LSE,
+            SyncSource::UsbSof => CFGR_SYNCSRC_USB,
+        }
+    }
+}
+
+impl SyncSource {
+    fn from_mask(mask: u32) -> Self {
+        match mask {
+            CFGR_SYNCSRC_GPIO => SyncSource::Gpio,
+            CFGR_SYNCSRC_LSE => SyncSource::Lse,
+            CFGR_SYNCSRC_USB => SyncSource::UsbSof,
+            _ => panic!("SyncSource::from_mask - mask was not a valid value!"),
+        }
+    }
+}
+
+/// The factor the sync signal is divided by before it reaches the CRS.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SyncDivider {
+    /// Not divided
+    Div1,
+    /// Divided by 2
+    Div2,
+    /// Divided by 4
+    Div4,
+    /// Divided by 8
+    Div8,
+    /// Divided by 16
+    Div16,
+    /// Divided by 32
+    Div32,
+    /// Divided by 64
+    Div64,
+    /// Divided by 128
+    Div128,
+}
+
+impl Field for SyncDivider {
+    fn mask(&self) -> u32 {
+        (*self as u32) << 24
+    }
+}
+
+impl SyncDivider {
+    fn from_mask(mask: u32) -> Self {
+        match mask >> 24 {
+            0 => SyncDivider::Div1,
+            1 => SyncDivider::Div2,
+            2 => SyncDivider::Div4,
+            3 => SyncDivider::Div8,
+            4 => SyncDivider::Div16,
+            5 => SyncDivider::Div32,
+            6 => SyncDivider::Div64,
+            7 => SyncDivider::Div128,
+            _ => panic!("SyncDivider::from_mask - mask was not a valid value!"),
+        }
+    }
+}
+
+/// The CR register
+#[derive(Copy, Clone, Debug)]
+pub struct CR(u32);
+
+impl CR {
+    /// Turn the frequency error counter on if `enable` is true, off otherwise.
+    pub fn set_counter(&mut self, enable: bool) {
+        self.0 &= !CR_CEN;
+        if enable {
+            self.0 |= CR_CEN;
+        }
+    }
+
+    pub fn counter_is_on(&self) -> bool {
+        (self.0 & CR_CEN) != 0
+    }
+
+    /// Let the hardware adjust the trim value automatically if `enable` is true.
+    pub fn set_auto_trim(&mut self, enable: bool) {
+        self.0 &= !CR_AUTOTRIMEN;
+        if enable {
+            self.0 |= CR_AUTOTRIMEN;
+        }
+    }
+
+    pub fn auto_trim_is_on(&self) -> bool {
+        (self.0 & CR_AUTOTRIMEN) != 0
+    }
+
+    /// Set the HSI48 trim value. The value MUST be within the range of [0..63], if it is not the
+    /// kernel will panic.
+    pub fn set_trim(&mut self, trim: u8) {
+        if trim > CR_TRIM_MAX {
+            panic!("CR::set_trim - trim must be in the range [0..63]!");
+        }
+        self.0 &= !CR_TRIM_MASK;
+        self.0 |= (trim as u32) << 8;
+    }
+
+    pub fn get_trim(&self) -> u8 {
+        ((self.0 & CR_TRIM_MASK) >> 8) as u8
+    }
+
+    /// Turn the sync ok, sync warning, error and expected sync interrupts on if `enable` is true,
+    /// off otherwise.
+    pub fn set_interrupts(&mut self, enable: bool) {
+        let mask = CR_SYNCOKIE | CR_SYNCWARNIE | CR_ERRIE | CR_ESYNCIE;
+        self.0 &= !mask;
+        if enable {
+            self.0 |= mask;
+        }
+    }
+
+    /// Generate a sync event from software.
+    pub fn generate_sync(&mut self) {
+        self.0 |= CR_SWSYNC;
+    }
+}
+
+/// The CFGR register
+#[derive(Copy, Clone, Debug)]
+pub struct CFGR(u32);
+
+impl CFGR {
+    pub fn set_sync_source(&mut self, source: SyncSource) {
+        self.0 &= !CFGR_SYNCSRC_MASK;
+        self.0 |= source.mask();
+    }
+
+    pub fn get_sync_source(&self) -> SyncSource {
+        SyncSource::from_mask(self.0 & CFGR_SYNCSRC_MASK)
+    }
+
+    pub fn set_sync_divider(&mut self, divider: SyncDivider) {
+        self.0 &= !CFGR_SYNCDIV_MASK;
+        self.0 |= divider.mask();
+    }
+
+    pub fn get_sync_divider(&self) -> SyncDivider {
+        SyncDivider::from_mask(self.0 & CFGR_SYNCDIV_MASK)
+    }
+
+    /// Set the sync signal to be detected on its falling edge if `falling` is true, rising edge
+    /// otherwise.
+    pub fn set_sync_polarity(&mut self, falling: bool) {
+        self.0 &= !CFGR_SYNCPOL;
+        if falling {
+            self.0 |= CFGR_SYNCPOL;
+        }
+    }
+
+    /// Set the value the frequency error counter reloads with on every sync event.
+    pub fn set_reload(&mut self, reload: u16) {
+        self.0 &= !CFGR_RELOAD_MASK;
+        self.0 |= reload as u32;
+    }
+
+    pub fn get_reload(&self) -> u16 {
+        (self.0 & CFGR_RELOAD_MASK) as u16
+    }
+
+    /// Set the frequency error limit, errors within the limit are trimmed out without a warning.
+    pub fn set_error_limit(&mut self, limit: u8) {
+        self.0 &= !CFGR_FELIM_MASK;
+        self.0 |= (limit as u32) << 16;
+    }
+
+    pub fn get_error_limit(&self) -> u8 {
+        ((self.0 & CFGR_FELIM_MASK) >> 16) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cr_set_counter() {
+        let mut cr = CR(0);
+
+        cr.set_counter(true);
+        assert_eq!(cr.0, 0b1 << 5);
+        assert!(cr.counter_is_on());
+
+        cr.set_counter(false);
+        assert_eq!(cr.0, 0);
+    }
+
+    #[test]
+    fn test_cr_set_auto_trim() {
+        let mut cr = CR(0);
+
+        cr.set_auto_trim(true);
+        assert_eq!(cr.0, 0b1 << 6);
+        assert!(cr.auto_trim_is_on());
+    }
+
+    #[test]
+    fn test_cr_set_trim() {
+        // Reset value of the trim is 32
+        let mut cr = CR(0x0000_2000);
+        assert_eq!(cr.get_trim(), 32);
+
+        cr.set_trim(0b11_1111);
+        assert_eq!(cr.0, 0b11_1111 << 8);
+        assert_eq!(cr.get_trim(), 63);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cr_set_trim_out_of_range_panics() {
+        CR(0).set_trim(64);
+    }
+
+    #[test]
+    fn test_cr_set_interrupts() {
+        let mut cr = CR(0);
+
+        cr.set_interrupts(true);
+        assert_eq!(cr.0, 0b1111);
+
+        cr.set_interrupts(false);
+        assert_eq!(cr.0, 0);
+    }
+
+    #[test]
+    fn test_cfgr_set_sync_source() {
+        // Reset value selects the USB SOF
+        let mut cfgr = CFGR(0x2022_BB7F);
+        assert_eq!(cfgr.get_sync_source(), SyncSource::UsbSof);
+
+        cfgr.set_sync_source(SyncSource::Lse);
+        assert_eq!(cfgr.0, 0x1022_BB7F);
+        assert_eq!(cfgr.get_sync_source(), SyncSource::Lse);
+    }
+
+    #[test]
+    fn test_cfgr_set_sync_divider() {
+        let mut cfgr = CFGR(0);
+
+        cfgr.set_sync_divider(SyncDivider::Div128);
+        assert_eq!(cfgr.0, 0b111 << 24);
+        assert_eq!(cfgr.get_sync_divider(), SyncDivider::Div128);
+    }
+
+    #[test]
+    fn test_cfgr_set_reload_and_error_limit() {
+        let mut cfgr = CFGR(0x2022_BB7F);
+        assert_eq!(cfgr.get_reload(), 47_999);
+        assert_eq!(cfgr.get_error_limit(), 34);
+
+        cfgr.set_reload(1464);
+        cfgr.set_error_limit(1);
+        assert_eq!(cfgr.0, 0x2001_05B8);
+    }
+}
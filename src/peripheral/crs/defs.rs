@@ -0,0 +1,61 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+pub const CRS_ADDR: *const u32 = 0x4000_6C00 as *const _;
+
+// The HSI48 rate the CRS trims towards
+pub const CRS_TARGET_RATE: u32 = 48_000_000;
+pub const USB_SOF_RATE: u32 = 1_000;
+pub const LSE_RATE: u32 = 32_768;
+
+pub const CR_OFFSET: u32 = 0x00;
+pub const CR_SYNCOKIE: u32 = 0b1;
+pub const CR_SYNCWARNIE: u32 = 0b1 << 1;
+pub const CR_ERRIE: u32 = 0b1 << 2;
+pub const CR_ESYNCIE: u32 = 0b1 << 3;
+pub const CR_CEN: u32 = 0b1 << 5;
+pub const CR_AUTOTRIMEN: u32 = 0b1 << 6;
+pub const CR_SWSYNC: u32 = 0b1 << 7;
+pub const CR_TRIM_MASK: u32 = 0b11_1111 << 8;
+pub const CR_TRIM_MAX: u8 = 0b11_1111;
+
+pub const CFGR_OFFSET: u32 = 0x04;
+pub const CFGR_RELOAD_MASK: u32 = 0xFFFF;
+pub const CFGR_FELIM_MASK: u32 = 0xFF << 16;
+pub const CFGR_SYNCDIV_MASK: u32 = 0b111 << 24;
+pub const CFGR_SYNCSRC_MASK: u32 = 0b11 << 28;
+pub const CFGR_SYNCSRC_GPIO: u32 = 0b00 << 28;
+pub const CFGR_SYNCSRC_LSE: u32 = 0b01 << 28;
+pub const CFGR_SYNCSRC_USB: u32 = 0b10 << 28;
+pub const CFGR_SYNCPOL: u32 = 0b1 << 31;
+
+pub const ISR_OFFSET: u32 = 0x08;
+pub const ISR_SYNCOKF: u32 = 0b1;
+pub const ISR_SYNCWARNF: u32 = 0b1 << 1;
+pub const ISR_ERRF: u32 = 0b1 << 2;
+pub const ISR_ESYNCF: u32 = 0b1 << 3;
+pub const ISR_SYNCERR: u32 = 0b1 << 8;
+pub const ISR_SYNCMISS: u32 = 0b1 << 9;
+pub const ISR_TRIMOVF: u32 = 0b1 << 10;
+pub const ISR_FEDIR: u32 = 0b1 << 15;
+pub const ISR_FECAP_MASK: u32 = 0xFFFF << 16;
+
+pub const ICR_OFFSET: u32 = 0x0C;
+pub const ICR_SYNCOKC: u32 = 0b1;
+pub const ICR_SYNCWARNC: u32 = 0b1 << 1;
+pub const ICR_ERRC: u32 = 0b1 << 2;
+pub const ICR_ESYNCC: u32 = 0b1 << 3;
@@ -0,0 +1,395 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module controls the CRS (Clock Recovery System). The CRS trims the HSI48 against an
+//! accurate reference signal, so the HSI48 can be used to run the system clock or USB without an
+//! external crystal.
+//!
+//! Example Usage:
+//! ```
+//!   // Trim against the USB start of frame packets and run the system clock from the HSI48
+//!   crs::init(SyncSource::UsbSof, crs::USB_SOF_RATE).expect("Invalid sync rate!");
+//!   rcc::ClockConfig::new(Clock::HSI48).apply(&mut peripherals.rcc)
+//!       .expect("Invalid clock configuration!");
+//! ```
+
+mod control;
+mod status;
+mod defs;
+
+use core::ops::{Deref, DerefMut};
+use volatile::Volatile;
+use altos_core::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use interrupt::{self, Hardware};
+use peripheral::rcc::{self, Clock, Peripheral};
+use self::defs::*;
+use self::control::{CR, CFGR};
+use self::status::{ISR, ICR};
+
+pub use self::control::{SyncSource, SyncDivider};
+pub use self::defs::{USB_SOF_RATE, LSE_RATE};
+
+/// Errors that can occur when starting the CRS.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CrsError {
+    /// The sync rate (in Hz) is zero, or too fast or too slow for the counter reload value to
+    /// fit in 16 bits.
+    InvalidSyncRate(u32),
+}
+
+/// The result of the most recent synchronization.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SyncStatus {
+    /// No sync event has happened yet.
+    NotSynced,
+    /// The HSI48 was within the frequency error limit.
+    Ok,
+    /// The HSI48 was outside of the frequency error limit, but was trimmed back towards it.
+    Warning,
+    /// The HSI48 couldn't be trimmed, see the error counts for the cause.
+    Error,
+}
+
+/// The number of synchronization problems seen since the counts were last reset.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ErrorCounts {
+    /// Sync events where the frequency was outside of the error limit.
+    pub warnings: usize,
+    /// Sync events where the frequency was too far off to be trimmed.
+    pub sync_errors: usize,
+    /// Sync events that didn't arrive when expected.
+    pub missed_syncs: usize,
+    /// Times the trim value hit the end of its range.
+    pub trim_overflows: usize,
+}
+
+static SYNC_STATUS: AtomicUsize = ATOMIC_USIZE_INIT;
+static WARNINGS: AtomicUsize = ATOMIC_USIZE_INIT;
+static SYNC_ERRORS: AtomicUsize = ATOMIC_USIZE_INIT;
+static MISSED_SYNCS: AtomicUsize = ATOMIC_USIZE_INIT;
+static TRIM_OVERFLOWS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Returns an instance of the CRS to modify the HSI48 trimming.
+///
+/// This is unsafe because it creates a second owner of the CRS, application code should get it
+/// from `Peripherals::take` instead.
+#[doc(hidden)]
+pub unsafe fn crs() -> CRS {
+    CRS::crs()
+}
+
+/// Start trimming the HSI48 against `source`, which runs at `sync_rate` Hz.
+///
+/// This turns on the HSI48 and the CRS, sets the reload value and error limit for the sync rate
+/// and enables automatic trimming. The sync status and error counts are kept up to date from the
+/// RCC and CRS interrupt. If the LSE is the source, it must already be running.
+///
+/// Returns an error without touching the hardware if no reload value matches the sync rate.
+pub fn init(source: SyncSource, sync_rate: u32) -> Result<(), CrsError> {
+    let reload = try!(reload_value(sync_rate));
+
+    // UNSAFE: The kernel drives the CRS once it is started, only the CRS and HSI48 are changed
+    let (mut rcc, mut nvic) = unsafe { (rcc::rcc(), interrupt::nvic()) };
+    rcc.enable_peripheral(Peripheral::ClockRecoverySystem);
+    rcc.enable_clock(Clock::HSI48);
+    while !rcc.clock_is_ready(Clock::HSI48) {}
+
+    let mut crs = CRS::crs();
+    crs.disable();
+    crs.set_sync_source(source);
+    crs.set_sync_divider(SyncDivider::Div1);
+    crs.set_reload(reload);
+    crs.set_error_limit(error_limit(sync_rate));
+    crs.enable_auto_trim();
+
+    reset_error_counts();
+    SYNC_STATUS.store(SyncStatus::NotSynced as usize, Ordering::Relaxed);
+
+    interrupt::register_handler(Hardware::Rcccrs, crs_handler);
+    nvic.enable_interrupt(Hardware::Rcccrs);
+    crs.enable_interrupts();
+
+    crs.enable();
+    Ok(())
+}
+
+/// Get the result of the most recent synchronization.
+pub fn sync_status() -> SyncStatus {
+    match SYNC_STATUS.load(Ordering::Relaxed) {
+        1 => SyncStatus::Ok,
+        2 => SyncStatus::Warning,
+        3 => SyncStatus::Error,
+        _ => SyncStatus::NotSynced,
+    }
+}
+
+/// Get the number of synchronization problems seen since the counts were last reset.
+pub fn error_counts() -> ErrorCounts {
+    ErrorCounts {
+        warnings: WARNINGS.load(Ordering::Relaxed),
+        sync_errors: SYNC_ERRORS.load(Ordering::Relaxed),
+        missed_syncs: MISSED_SYNCS.load(Ordering::Relaxed),
+        trim_overflows: TRIM_OVERFLOWS.load(Ordering::Relaxed),
+    }
+}
+
+/// Reset all of the error counts to zero.
+pub fn reset_error_counts() {
+    WARNINGS.store(0, Ordering::Relaxed);
+    SYNC_ERRORS.store(0, Ordering::Relaxed);
+    MISSED_SYNCS.store(0, Ordering::Relaxed);
+    TRIM_OVERFLOWS.store(0, Ordering::Relaxed);
+}
+
+/// Calculate the counter reload value for `sync_rate`, which is the number of HSI48 counts in one
+/// sync period minus one (`f_target / f_sync - 1`, rounded to the nearest count).
+fn reload_value(sync_rate: u32) -> Result<u16, CrsError> {
+    if sync_rate == 0 {
+        return Err(CrsError::InvalidSyncRate(sync_rate));
+    }
+    let counts = (CRS_TARGET_RATE as u64 + sync_rate as u64 / 2) / sync_rate as u64;
+    if counts == 0 || counts > CFGR_RELOAD_MASK as u64 + 1 {
+        return Err(CrsError::InvalidSyncRate(sync_rate));
+    }
+    Ok((counts - 1) as u16)
+}
+
+/// Calculate the frequency error limit for a sync rate, using the typical trim step of 0.14%.
+///
+/// The sync rate must be non-zero, this is checked by `reload_value`.
+fn error_limit(sync_rate: u32) -> u8 {
+    // (target / sync_rate) * 0.14% / 2, rounded
+    let sync_rate = sync_rate as u64;
+    let limit = (CRS_TARGET_RATE as u64 * 7 + sync_rate * 5000) / (sync_rate * 10000);
+    match limit {
+        0 => 1,
+        1...255 => limit as u8,
+        _ => 255,
+    }
+}
+
+fn crs_handler() {
+    let mut crs = CRS::crs();
+    let isr = crs.isr;
+
+    if isr.get_error() {
+        if isr.get_sync_error() {
+            SYNC_ERRORS.fetch_add(1, Ordering::Relaxed);
+        }
+        if isr.get_sync_missed() {
+            MISSED_SYNCS.fetch_add(1, Ordering::Relaxed);
+        }
+        if isr.get_trim_overflow() {
+            TRIM_OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+        }
+        SYNC_STATUS.store(SyncStatus::Error as usize, Ordering::Relaxed);
+        crs.icr.clear_error();
+    }
+    else if isr.get_sync_warning() {
+        WARNINGS.fetch_add(1, Ordering::Relaxed);
+        SYNC_STATUS.store(SyncStatus::Warning as usize, Ordering::Relaxed);
+    }
+    else if isr.get_sync_ok() {
+        SYNC_STATUS.store(SyncStatus::Ok as usize, Ordering::Relaxed);
+    }
+
+    if isr.get_sync_warning() {
+        crs.icr.clear_sync_warning();
+    }
+    if isr.get_sync_ok() {
+        crs.icr.clear_sync_ok();
+    }
+    if isr.get_expected_sync() {
+        crs.icr.clear_expected_sync();
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+#[doc(hidden)]
+pub struct RawCRS {
+    cr: CR,
+    cfgr: CFGR,
+    isr: ISR,
+    icr: ICR,
+}
+
+/// Clock Recovery System
+#[derive(Debug)]
+pub struct CRS(Volatile<RawCRS>);
+
+impl CRS {
+    fn crs() -> Self {
+        unsafe {
+            CRS(Volatile::new(CRS_ADDR as *const _))
+        }
+    }
+}
+
+impl Deref for CRS {
+    type Target = RawCRS;
+
+    fn deref(&self) -> &Self::Target {
+        &*(self.0)
+    }
+}
+
+impl DerefMut for CRS {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *(self.0)
+    }
+}
+
+impl RawCRS {
+    /// Turn on the frequency error counter, starting synchronization.
+    pub fn enable(&mut self) {
+        self.cr.set_counter(true);
+    }
+
+    /// Turn off the frequency error counter. The sync settings can only be changed while it is
+    /// off.
+    pub fn disable(&mut self) {
+        self.cr.set_counter(false);
+    }
+
+    /// Return true if the frequency error counter is on.
+    pub fn is_enabled(&self) -> bool {
+        self.cr.counter_is_on()
+    }
+
+    /// Let the hardware adjust the HSI48 trim after every sync event.
+    pub fn enable_auto_trim(&mut self) {
+        self.cr.set_auto_trim(true);
+    }
+
+    /// Stop the hardware from adjusting the HSI48 trim.
+    pub fn disable_auto_trim(&mut self) {
+        self.cr.set_auto_trim(false);
+    }
+
+    /// Get the current HSI48 trim value, in the range [0..63].
+    pub fn get_trim(&self) -> u8 {
+        self.cr.get_trim()
+    }
+
+    /// Set the HSI48 trim value. The value MUST be within the range of [0..63], if it is not the
+    /// kernel will panic. If automatic trimming is on the hardware will overwrite it.
+    pub fn set_trim(&mut self, trim: u8) {
+        self.cr.set_trim(trim);
+    }
+
+    /// Turn on the sync ok, sync warning, error and expected sync interrupts.
+    pub fn enable_interrupts(&mut self) {
+        self.cr.set_interrupts(true);
+    }
+
+    /// Turn off the CRS interrupts.
+    pub fn disable_interrupts(&mut self) {
+        self.cr.set_interrupts(false);
+    }
+
+    /// Generate a sync event from software.
+    pub fn generate_sync(&mut self) {
+        self.cr.generate_sync();
+    }
+
+    /// Get the signal the HSI48 is synchronized against.
+    pub fn get_sync_source(&self) -> SyncSource {
+        self.cfgr.get_sync_source()
+    }
+
+    /// Set the signal the HSI48 is synchronized against.
+    pub fn set_sync_source(&mut self, source: SyncSource) {
+        self.cfgr.set_sync_source(source);
+    }
+
+    /// Get the factor the sync signal is divided by.
+    pub fn get_sync_divider(&self) -> SyncDivider {
+        self.cfgr.get_sync_divider()
+    }
+
+    /// Set the factor the sync signal is divided by.
+    pub fn set_sync_divider(&mut self, divider: SyncDivider) {
+        self.cfgr.set_sync_divider(divider);
+    }
+
+    /// Detect the sync signal on its falling edge if `falling` is true, rising edge otherwise.
+    pub fn set_sync_polarity(&mut self, falling: bool) {
+        self.cfgr.set_sync_polarity(falling);
+    }
+
+    /// Get the value the frequency error counter reloads with on every sync event.
+    pub fn get_reload(&self) -> u16 {
+        self.cfgr.get_reload()
+    }
+
+    /// Set the value the frequency error counter reloads with on every sync event.
+    pub fn set_reload(&mut self, reload: u16) {
+        self.cfgr.set_reload(reload);
+    }
+
+    /// Get the frequency error limit.
+    pub fn get_error_limit(&self) -> u8 {
+        self.cfgr.get_error_limit()
+    }
+
+    /// Set the frequency error limit.
+    pub fn set_error_limit(&mut self, limit: u8) {
+        self.cfgr.set_error_limit(limit);
+    }
+
+    /// Get the frequency error captured at the last sync event. The error is positive if the
+    /// HSI48 was running fast, negative if it was running slow.
+    pub fn get_frequency_error(&self) -> i32 {
+        self.isr.get_frequency_error()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usb_sof_matches_reset_values() {
+        assert_eq!(reload_value(USB_SOF_RATE), Ok(47_999));
+        assert_eq!(error_limit(USB_SOF_RATE), 34);
+    }
+
+    #[test]
+    fn test_lse_reload_and_error_limit() {
+        assert_eq!(reload_value(LSE_RATE), Ok(1464));
+        assert_eq!(error_limit(LSE_RATE), 1);
+    }
+
+    #[test]
+    fn test_zero_sync_rate_is_invalid() {
+        assert_eq!(reload_value(0), Err(CrsError::InvalidSyncRate(0)));
+    }
+
+    #[test]
+    fn test_slow_sync_rate_is_invalid() {
+        assert_eq!(reload_value(733), Ok(65_483));
+        assert_eq!(reload_value(732), Err(CrsError::InvalidSyncRate(732)));
+    }
+
+    #[test]
+    fn test_fast_sync_rate_does_not_overflow() {
+        assert_eq!(reload_value(1_000_000), Ok(47));
+        assert_eq!(error_limit(1_000_000), 1);
+        assert_eq!(reload_value(200_000_000), Err(CrsError::InvalidSyncRate(200_000_000)));
+    }
+}
@@ -0,0 +1,134 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module handles the ISR and ICR registers, which report the state of the synchronization.
+
+use super::defs::*;
+
+/// The ISR register
+#[derive(Copy, Clone, Debug)]
+pub struct ISR(u32);
+
+impl ISR {
+    /// Return true if the last sync event found the frequency within the error limit.
+    pub fn get_sync_ok(&self) -> bool {
+        (self.0 & ISR_SYNCOKF) != 0
+    }
+
+    /// Return true if the last sync event found the frequency outside of the error limit, but
+    /// still close enough to be trimmed.
+    pub fn get_sync_warning(&self) -> bool {
+        (self.0 & ISR_SYNCWARNF) != 0
+    }
+
+    /// Return true if a sync error, missed sync or trim overflow has occurred.
+    pub fn get_error(&self) -> bool {
+        (self.0 & ISR_ERRF) != 0
+    }
+
+    pub fn get_expected_sync(&self) -> bool {
+        (self.0 & ISR_ESYNCF) != 0
+    }
+
+    pub fn get_sync_error(&self) -> bool {
+        (self.0 & ISR_SYNCERR) != 0
+    }
+
+    pub fn get_sync_missed(&self) -> bool {
+        (self.0 & ISR_SYNCMISS) != 0
+    }
+
+    pub fn get_trim_overflow(&self) -> bool {
+        (self.0 & ISR_TRIMOVF) != 0
+    }
+
+    /// Get the frequency error captured at the last sync event, in counter steps. The error is
+    /// positive if the HSI48 was running fast, negative if it was running slow.
+    pub fn get_frequency_error(&self) -> i32 {
+        let error = ((self.0 & ISR_FECAP_MASK) >> 16) as i32;
+        if (self.0 & ISR_FEDIR) != 0 {
+            -error
+        }
+        else {
+            error
+        }
+    }
+}
+
+/// The ICR register
+#[derive(Copy, Clone, Debug)]
+pub struct ICR(u32);
+
+impl ICR {
+    // The register is write 1 to clear, so only the flag being cleared is written
+
+    pub fn clear_sync_ok(&mut self) {
+        self.0 = ICR_SYNCOKC;
+    }
+
+    pub fn clear_sync_warning(&mut self) {
+        self.0 = ICR_SYNCWARNC;
+    }
+
+    /// Clear the error flag, along with the sync error, missed sync and trim overflow flags.
+    pub fn clear_error(&mut self) {
+        self.0 = ICR_ERRC;
+    }
+
+    pub fn clear_expected_sync(&mut self) {
+        self.0 = ICR_ESYNCC;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isr_flags() {
+        let isr = ISR(0b111 << 8 | 0b1111);
+
+        assert!(isr.get_sync_ok());
+        assert!(isr.get_sync_warning());
+        assert!(isr.get_error());
+        assert!(isr.get_expected_sync());
+        assert!(isr.get_sync_error());
+        assert!(isr.get_sync_missed());
+        assert!(isr.get_trim_overflow());
+
+        let isr = ISR(0);
+        assert!(!isr.get_sync_ok());
+        assert!(!isr.get_error());
+    }
+
+    #[test]
+    fn test_isr_get_frequency_error() {
+        assert_eq!(ISR(0x0022_0000).get_frequency_error(), 34);
+        assert_eq!(ISR(0x0022_8000).get_frequency_error(), -34);
+    }
+
+    #[test]
+    fn test_icr_only_writes_one_bit() {
+        let mut icr = ICR(0);
+
+        icr.clear_sync_warning();
+        assert_eq!(icr.0, 0b1 << 1);
+
+        icr.clear_error();
+        assert_eq!(icr.0, 0b1 << 2);
+    }
+}
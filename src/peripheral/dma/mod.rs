@@ -361,10 +361,9 @@ impl RawDMA {
 /// Set the clock for the DMA and makes the necessary calls in order to configure
 /// peripherals intended for use with the DMA.
 pub fn init() {
-    let mut rcc = rcc::rcc();
+    // UNSAFE: The kernel owns the DMA, only its clock and interrupt are changed
+    let (mut rcc, mut nvic) = unsafe { (rcc::rcc(), interrupt::nvic()) };
     rcc.enable_peripheral(rcc::Peripheral::DMA);
-
-    let mut nvic = interrupt::nvic();
     nvic.enable_interrupt(interrupt::Hardware::Dmach4Plus);
 }
 
@@ -0,0 +1,139 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use super::super::Field;
+use super::defs::*;
+
+/// The number of wait states inserted when reading from flash.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Latency {
+    /// Zero wait states, if the system clock is 24 MHz or less.
+    Zero,
+    /// One wait state, if the system clock is above 24 MHz.
+    One,
+}
+
+impl Field for Latency {
+    fn mask(&self) -> u32 {
+        match *self {
+            Latency::Zero => ACR_LATENCY_ZERO,
+            Latency::One => ACR_LATENCY_ONE,
+        }
+    }
+}
+
+impl Latency {
+    fn from_mask(mask: u32) -> Self {
+        match mask {
+            ACR_LATENCY_ZERO => Latency::Zero,
+            ACR_LATENCY_ONE => Latency::One,
+            _ => panic!("Latency::from_mask - mask was not a valid value!"),
+        }
+    }
+
+    /// Get the lowest latency that is in spec for the specified system clock rate.
+    pub fn for_system_clock(rate: u32) -> Self {
+        if rate <= LATENCY_ZERO_MAX {
+            Latency::Zero
+        }
+        else {
+            Latency::One
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ACR(u32);
+
+impl ACR {
+    pub fn set_latency(&mut self, latency: Latency) {
+        let mask = latency.mask();
+
+        self.0 &= !ACR_LATENCY_MASK;
+        self.0 |= mask;
+    }
+
+    pub fn get_latency(&self) -> Latency {
+        Latency::from_mask(self.0 & ACR_LATENCY_MASK)
+    }
+
+    pub fn set_prefetch(&mut self, enable: bool) {
+        self.0 &= !ACR_PRFTBE;
+        if enable {
+            self.0 |= ACR_PRFTBE;
+        }
+    }
+
+    pub fn prefetch_is_enabled(&self) -> bool {
+        self.0 & ACR_PRFTBS != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acr_set_latency() {
+        let mut acr = ACR(0);
+
+        acr.set_latency(Latency::One);
+        assert_eq!(acr.0, 0b001);
+
+        acr.set_latency(Latency::Zero);
+        assert_eq!(acr.0, 0);
+    }
+
+    #[test]
+    fn test_acr_set_latency_doesnt_change_prefetch() {
+        let mut acr = ACR(0b1 << 4);
+
+        acr.set_latency(Latency::One);
+        assert_eq!(acr.0, 0b1 << 4 | 0b001);
+    }
+
+    #[test]
+    fn test_acr_get_latency() {
+        let acr = ACR(0b1 << 4 | 0b001);
+
+        assert_eq!(acr.get_latency(), Latency::One);
+    }
+
+    #[test]
+    fn test_acr_set_prefetch() {
+        let mut acr = ACR(0);
+
+        acr.set_prefetch(true);
+        assert_eq!(acr.0, 0b1 << 4);
+
+        acr.set_prefetch(false);
+        assert_eq!(acr.0, 0);
+    }
+
+    #[test]
+    fn test_acr_prefetch_is_enabled_reads_status_bit() {
+        assert_eq!(ACR(0b1 << 4).prefetch_is_enabled(), false);
+        assert_eq!(ACR(0b1 << 5).prefetch_is_enabled(), true);
+    }
+
+    #[test]
+    fn test_latency_for_system_clock() {
+        assert_eq!(Latency::for_system_clock(8_000_000), Latency::Zero);
+        assert_eq!(Latency::for_system_clock(24_000_000), Latency::Zero);
+        assert_eq!(Latency::for_system_clock(48_000_000), Latency::One);
+    }
+}
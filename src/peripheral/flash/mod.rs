@@ -0,0 +1,132 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module controls the flash memory interface. It handles the number of wait states used
+//! when reading from flash and the prefetch buffer.
+//!
+//! The wait states have to match the system clock rate, so changing the system clock through the
+//! RCC updates them automatically.
+
+mod acr;
+mod defs;
+
+use core::ops::{Deref, DerefMut};
+use volatile::Volatile;
+use arm::asm::dsb;
+use self::defs::*;
+use self::acr::ACR;
+
+pub use self::acr::Latency;
+
+/// Returns an instance of the flash interface to modify flash access settings.
+///
+/// This is unsafe because it creates a second owner of the flash interface, application code
+/// should get it from `Peripherals::take` instead.
+#[doc(hidden)]
+pub unsafe fn flash() -> Flash {
+    Flash::flash()
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+#[doc(hidden)]
+pub struct RawFlash {
+    acr: ACR,
+    keyr: u32,
+    optkeyr: u32,
+    sr: u32,
+    cr: u32,
+    ar: u32,
+    _reserved: u32,
+    obr: u32,
+    wrpr: u32,
+}
+
+/// Flash memory interface
+#[derive(Debug)]
+pub struct Flash(Volatile<RawFlash>);
+
+impl Flash {
+    fn flash() -> Self {
+        unsafe {
+            Flash(Volatile::new(FLASH_ADDR as *const _))
+        }
+    }
+}
+
+impl Deref for Flash {
+    type Target = RawFlash;
+
+    fn deref(&self) -> &Self::Target {
+        &*(self.0)
+    }
+}
+
+impl DerefMut for Flash {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *(self.0)
+    }
+}
+
+impl RawFlash {
+    /// Set the number of wait states used when reading from flash.
+    ///
+    /// The latency must be in spec for the current system clock rate, this should not need to be
+    /// called directly since the RCC keeps it up to date when the system clock changes.
+    pub fn set_latency(&mut self, latency: Latency) {
+        self.acr.set_latency(latency);
+        // Make sure the new latency is in effect before anything depending on it happens
+        unsafe { dsb(); }
+    }
+
+    /// Get the number of wait states used when reading from flash.
+    pub fn get_latency(&self) -> Latency {
+        self.acr.get_latency()
+    }
+
+    /// Enable the prefetch buffer.
+    pub fn enable_prefetch(&mut self) {
+        self.acr.set_prefetch(true);
+    }
+
+    /// Disable the prefetch buffer.
+    pub fn disable_prefetch(&mut self) {
+        self.acr.set_prefetch(false);
+    }
+
+    /// Return true if the prefetch buffer is enabled.
+    pub fn prefetch_is_enabled(&self) -> bool {
+        self.acr.prefetch_is_enabled()
+    }
+
+    /// Prepare the flash interface for a change in the system clock rate.
+    ///
+    /// If the clock is speeding up, this should be called before the change. If it is slowing
+    /// down, it should be called after. The prefetch buffer is enabled whenever wait states are
+    /// needed.
+    pub fn configure_for_system_clock(&mut self, rate: u32) {
+        let latency = Latency::for_system_clock(rate);
+        if latency == Latency::Zero {
+            self.set_latency(latency);
+            self.disable_prefetch();
+        }
+        else {
+            self.enable_prefetch();
+            self.set_latency(latency);
+        }
+    }
+}
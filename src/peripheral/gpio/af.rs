@@ -0,0 +1,384 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module maps peripheral signals to the pins and alternate functions that carry them.
+//!
+//! Each signal is a trait that is only implemented for a pin that can carry it, set to the right
+//! alternate function. Drivers take pins through these traits, so connecting a signal to the wrong
+//! pin or function is caught at compile time.
+//!
+//! The Usart signals are also available as `UsartTx<U>` and friends, generic over the Usart `U`,
+//! so a driver can take the pins for whichever Usart it is opening.
+//!
+//! Example Usage:
+//! ```
+//!   fn connect<TX: Usart1Tx, RX: Usart1Rx>(tx: TX, rx: RX) { ... }
+//!
+//!   let pins = peripherals.gpioa.split().pins;
+//!   let (tx, rx) = (pins.p9.unwrap(), pins.p10.unwrap());
+//!   connect(tx.into_alternate::<AF1>(), rx.into_alternate::<AF1>());
+//!   // connect(tx.into_alternate::<AF4>(), ...) does not compile, AF4 on PA9 is I2C1_SCL
+//! ```
+
+use super::{Speed, Type, Pull};
+use super::pin::{Pin, Alternate, AF0, AF1, AF2, AF4, AF5};
+use super::pin_id::*;
+use peripheral::usart::{Usart1, Usart2};
+#[cfg(feature="stm32f09x")]
+use peripheral::usart::{Usart3, Usart4, Usart5, Usart6, Usart7, Usart8};
+
+/// A pin connected to an alternate function, which is what every signal is carried on.
+pub trait SignalPin {
+    /// Set the speed, output type and pull behavior the signal needs.
+    fn configure(&mut self, speed: Speed, p_type: Type, pull: Pull);
+}
+
+impl<AF, ID> SignalPin for Pin<Alternate<AF>, ID> {
+    fn configure(&mut self, speed: Speed, p_type: Type, pull: Pull) {
+        self.set_speed(speed);
+        self.set_type(p_type);
+        self.set_pull(pull);
+    }
+}
+
+/// The transmit signal of Usart `U`.
+pub trait UsartTx<U>: SignalPin {}
+/// The receive signal of Usart `U`.
+pub trait UsartRx<U>: SignalPin {}
+/// The clear to send signal of Usart `U`.
+pub trait UsartCts<U>: SignalPin {}
+/// The request to send signal of Usart `U`.
+pub trait UsartRts<U>: SignalPin {}
+
+macro_rules! signals {
+    ($($(#[$attr:meta])* $signal:ident,)*) => {
+        $(
+            $(#[$attr])*
+            pub trait $signal: SignalPin {}
+        )*
+    }
+}
+
+macro_rules! usart_signals {
+    ($($(#[$attr:meta])* $usart:ident: [$tx:ident, $rx:ident, $cts:ident, $rts:ident],)*) => {
+        $(
+            $(#[$attr])*
+            impl<P: $tx> UsartTx<$usart> for P {}
+            $(#[$attr])*
+            impl<P: $rx> UsartRx<$usart> for P {}
+            $(#[$attr])*
+            impl<P: $cts> UsartCts<$usart> for P {}
+            $(#[$attr])*
+            impl<P: $rts> UsartRts<$usart> for P {}
+        )*
+    }
+}
+
+macro_rules! af_map {
+    ($($(#[$attr:meta])* $signal:ident: [$($pin:ident => $af:ident),*],)*) => {
+        $($(
+            $(#[$attr])*
+            impl $signal for Pin<Alternate<$af>, $pin> {}
+        )*)*
+    }
+}
+
+signals! {
+    /// USART1 transmit
+    Usart1Tx,
+    /// USART1 receive
+    Usart1Rx,
+    /// USART1 clear to send
+    Usart1Cts,
+    /// USART1 request to send
+    Usart1Rts,
+    /// USART2 transmit
+    Usart2Tx,
+    /// USART2 receive
+    Usart2Rx,
+    /// USART2 clear to send
+    Usart2Cts,
+    /// USART2 request to send
+    Usart2Rts,
+    /// USART3 transmit
+    #[cfg(feature="stm32f09x")]
+    Usart3Tx,
+    /// USART3 receive
+    #[cfg(feature="stm32f09x")]
+    Usart3Rx,
+    /// USART3 clear to send
+    #[cfg(feature="stm32f09x")]
+    Usart3Cts,
+    /// USART3 request to send
+    #[cfg(feature="stm32f09x")]
+    Usart3Rts,
+    /// USART4 transmit
+    #[cfg(feature="stm32f09x")]
+    Usart4Tx,
+    /// USART4 receive
+    #[cfg(feature="stm32f09x")]
+    Usart4Rx,
+    /// USART4 clear to send
+    #[cfg(feature="stm32f09x")]
+    Usart4Cts,
+    /// USART4 request to send
+    #[cfg(feature="stm32f09x")]
+    Usart4Rts,
+    /// USART5 transmit
+    #[cfg(feature="stm32f09x")]
+    Usart5Tx,
+    /// USART5 receive
+    #[cfg(feature="stm32f09x")]
+    Usart5Rx,
+    /// USART5 clear to send
+    #[cfg(feature="stm32f09x")]
+    Usart5Cts,
+    /// USART5 request to send
+    #[cfg(feature="stm32f09x")]
+    Usart5Rts,
+    /// USART6 transmit
+    #[cfg(feature="stm32f09x")]
+    Usart6Tx,
+    /// USART6 receive
+    #[cfg(feature="stm32f09x")]
+    Usart6Rx,
+    /// USART6 clear to send
+    #[cfg(feature="stm32f09x")]
+    Usart6Cts,
+    /// USART6 request to send
+    #[cfg(feature="stm32f09x")]
+    Usart6Rts,
+    /// USART7 transmit
+    #[cfg(feature="stm32f09x")]
+    Usart7Tx,
+    /// USART7 receive
+    #[cfg(feature="stm32f09x")]
+    Usart7Rx,
+    /// USART7 clear to send
+    #[cfg(feature="stm32f09x")]
+    Usart7Cts,
+    /// USART7 request to send
+    #[cfg(feature="stm32f09x")]
+    Usart7Rts,
+    /// USART8 transmit
+    #[cfg(feature="stm32f09x")]
+    Usart8Tx,
+    /// USART8 receive
+    #[cfg(feature="stm32f09x")]
+    Usart8Rx,
+    /// USART8 clear to send
+    #[cfg(feature="stm32f09x")]
+    Usart8Cts,
+    /// USART8 request to send
+    #[cfg(feature="stm32f09x")]
+    Usart8Rts,
+
+    /// Microcontroller clock output
+    Mco,
+
+    /// SPI1 clock
+    Spi1Sck,
+    /// SPI1 master in, slave out
+    Spi1Miso,
+    /// SPI1 master out, slave in
+    Spi1Mosi,
+    /// SPI2 clock
+    Spi2Sck,
+    /// SPI2 master in, slave out
+    Spi2Miso,
+    /// SPI2 master out, slave in
+    Spi2Mosi,
+
+    /// I2C1 clock
+    I2c1Scl,
+    /// I2C1 data
+    I2c1Sda,
+    /// I2C2 clock
+    #[cfg(feature="stm32f09x")]
+    I2c2Scl,
+    /// I2C2 data
+    #[cfg(feature="stm32f09x")]
+    I2c2Sda,
+
+    /// TIM1 channel 1
+    Tim1Ch1,
+    /// TIM1 channel 2
+    Tim1Ch2,
+    /// TIM1 channel 3
+    Tim1Ch3,
+    /// TIM1 channel 4
+    Tim1Ch4,
+    /// TIM2 channel 1
+    Tim2Ch1,
+    /// TIM2 channel 2
+    Tim2Ch2,
+    /// TIM2 channel 3
+    Tim2Ch3,
+    /// TIM2 channel 4
+    Tim2Ch4,
+    /// TIM3 channel 1
+    Tim3Ch1,
+    /// TIM3 channel 2
+    Tim3Ch2,
+    /// TIM3 channel 3
+    Tim3Ch3,
+    /// TIM3 channel 4
+    Tim3Ch4,
+    /// TIM14 channel 1
+    Tim14Ch1,
+    /// TIM15 channel 1
+    #[cfg(feature="stm32f09x")]
+    Tim15Ch1,
+    /// TIM15 channel 2
+    #[cfg(feature="stm32f09x")]
+    Tim15Ch2,
+    /// TIM16 channel 1
+    Tim16Ch1,
+    /// TIM17 channel 1
+    Tim17Ch1,
+}
+
+af_map! {
+    Usart1Tx: [PA9 => AF1, PB6 => AF0],
+    Usart1Rx: [PA10 => AF1, PB7 => AF0],
+    Usart1Cts: [PA11 => AF1],
+    Usart1Rts: [PA12 => AF1],
+    Usart2Tx: [PA2 => AF1, PA14 => AF1],
+    Usart2Rx: [PA3 => AF1, PA15 => AF1],
+    Usart2Cts: [PA0 => AF1],
+    Usart2Rts: [PA1 => AF1],
+
+    Mco: [PA8 => AF0],
+
+    Spi1Sck: [PA5 => AF0, PB3 => AF0],
+    Spi1Miso: [PA6 => AF0, PB4 => AF0],
+    Spi1Mosi: [PA7 => AF0, PB5 => AF0],
+    Spi2Sck: [PB10 => AF5, PB13 => AF0],
+    Spi2Miso: [PB14 => AF0],
+    Spi2Mosi: [PB15 => AF0],
+
+    I2c1Scl: [PA9 => AF4, PB6 => AF1, PB8 => AF1],
+    I2c1Sda: [PA10 => AF4, PB7 => AF1, PB9 => AF1],
+
+    Tim1Ch1: [PA8 => AF2],
+    Tim1Ch2: [PA9 => AF2],
+    Tim1Ch3: [PA10 => AF2],
+    Tim1Ch4: [PA11 => AF2],
+    Tim2Ch1: [PA0 => AF2, PA5 => AF2, PA15 => AF2],
+    Tim2Ch2: [PA1 => AF2, PB3 => AF2],
+    Tim2Ch3: [PA2 => AF2, PB10 => AF2],
+    Tim2Ch4: [PA3 => AF2, PB11 => AF2],
+    Tim3Ch1: [PA6 => AF1, PB4 => AF1],
+    Tim3Ch2: [PA7 => AF1, PB5 => AF1],
+    Tim3Ch3: [PB0 => AF1],
+    Tim3Ch4: [PB1 => AF1],
+    Tim14Ch1: [PA4 => AF4, PA7 => AF4, PB1 => AF0],
+    Tim16Ch1: [PA6 => AF5, PB8 => AF2],
+    Tim17Ch1: [PA7 => AF5, PB9 => AF2],
+}
+
+// The STM32F042 only has PC13 to PC15, and no I2C2, TIM15 or Usarts past Usart2.
+af_map! {
+    #[cfg(feature="stm32f09x")]
+    Usart3Tx: [PB10 => AF4, PC4 => AF1, PC10 => AF1],
+    #[cfg(feature="stm32f09x")]
+    Usart3Rx: [PB11 => AF4, PC5 => AF1, PC11 => AF1],
+    #[cfg(feature="stm32f09x")]
+    Usart3Cts: [PA6 => AF4, PB13 => AF4],
+    #[cfg(feature="stm32f09x")]
+    Usart3Rts: [PB1 => AF4, PB14 => AF4],
+    #[cfg(feature="stm32f09x")]
+    Usart4Tx: [PA0 => AF4, PC10 => AF0],
+    #[cfg(feature="stm32f09x")]
+    Usart4Rx: [PA1 => AF4, PC11 => AF0],
+    #[cfg(feature="stm32f09x")]
+    Usart4Cts: [PB7 => AF4],
+    #[cfg(feature="stm32f09x")]
+    Usart4Rts: [PA15 => AF4],
+    #[cfg(feature="stm32f09x")]
+    Usart5Tx: [PB3 => AF4, PC12 => AF2],
+    #[cfg(feature="stm32f09x")]
+    Usart5Rx: [PB4 => AF4],
+    #[cfg(feature="stm32f09x")]
+    Usart5Cts: [],
+    #[cfg(feature="stm32f09x")]
+    Usart5Rts: [PB5 => AF4],
+    #[cfg(feature="stm32f09x")]
+    Usart6Tx: [PA4 => AF5, PC0 => AF2],
+    #[cfg(feature="stm32f09x")]
+    Usart6Rx: [PA5 => AF5, PC1 => AF2],
+    #[cfg(feature="stm32f09x")]
+    Usart6Cts: [],
+    #[cfg(feature="stm32f09x")]
+    Usart6Rts: [],
+    #[cfg(feature="stm32f09x")]
+    Usart7Tx: [PC0 => AF1, PC6 => AF1],
+    #[cfg(feature="stm32f09x")]
+    Usart7Rx: [PC1 => AF1, PC7 => AF1],
+    #[cfg(feature="stm32f09x")]
+    Usart7Cts: [],
+    #[cfg(feature="stm32f09x")]
+    Usart7Rts: [],
+    #[cfg(feature="stm32f09x")]
+    Usart8Tx: [PC2 => AF2, PC8 => AF1],
+    #[cfg(feature="stm32f09x")]
+    Usart8Rx: [PC3 => AF2, PC9 => AF1],
+    #[cfg(feature="stm32f09x")]
+    Usart8Cts: [],
+    #[cfg(feature="stm32f09x")]
+    Usart8Rts: [],
+
+    #[cfg(feature="stm32f09x")]
+    Spi2Miso: [PC2 => AF1],
+    #[cfg(feature="stm32f09x")]
+    Spi2Mosi: [PC3 => AF1],
+
+    #[cfg(feature="stm32f09x")]
+    I2c2Scl: [PA11 => AF5, PB10 => AF1, PB13 => AF5],
+    #[cfg(feature="stm32f09x")]
+    I2c2Sda: [PA12 => AF5, PB11 => AF1, PB14 => AF5],
+
+    #[cfg(feature="stm32f09x")]
+    Tim3Ch1: [PC6 => AF0],
+    #[cfg(feature="stm32f09x")]
+    Tim3Ch2: [PC7 => AF0],
+    #[cfg(feature="stm32f09x")]
+    Tim3Ch3: [PC8 => AF0],
+    #[cfg(feature="stm32f09x")]
+    Tim3Ch4: [PC9 => AF0],
+    #[cfg(feature="stm32f09x")]
+    Tim15Ch1: [PA2 => AF0, PB14 => AF1],
+    #[cfg(feature="stm32f09x")]
+    Tim15Ch2: [PA3 => AF0, PB15 => AF1],
+}
+
+usart_signals! {
+    Usart1: [Usart1Tx, Usart1Rx, Usart1Cts, Usart1Rts],
+    Usart2: [Usart2Tx, Usart2Rx, Usart2Cts, Usart2Rts],
+    #[cfg(feature="stm32f09x")]
+    Usart3: [Usart3Tx, Usart3Rx, Usart3Cts, Usart3Rts],
+    #[cfg(feature="stm32f09x")]
+    Usart4: [Usart4Tx, Usart4Rx, Usart4Cts, Usart4Rts],
+    #[cfg(feature="stm32f09x")]
+    Usart5: [Usart5Tx, Usart5Rx, Usart5Cts, Usart5Rts],
+    #[cfg(feature="stm32f09x")]
+    Usart6: [Usart6Tx, Usart6Rx, Usart6Cts, Usart6Rts],
+    #[cfg(feature="stm32f09x")]
+    Usart7: [Usart7Tx, Usart7Rx, Usart7Cts, Usart7Rts],
+    #[cfg(feature="stm32f09x")]
+    Usart8: [Usart8Tx, Usart8Rx, Usart8Cts, Usart8Rts],
+}
@@ -22,7 +22,7 @@ use super::defs::*;
 ///
 /// See data sheet for port mappings.
 #[allow(missing_docs)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum AlternateFunction {
     Zero,
     One,
@@ -0,0 +1,50 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+/// The bit reset register, writing a 1 to a bit drives that pin low.
+#[derive(Copy, Clone, Debug)]
+pub struct BRR(u32);
+
+impl BRR {
+    /// Set the specified port low, port must be a value between [0..15] or the kernel will panic.
+    pub fn reset(&mut self, port: u8) {
+        if port > 15 {
+            panic!("BRR::reset - specified port must be between [0..15]!");
+        }
+        // Writing 0 has no effect, so only the bit for this port is written
+        self.0 = 0b1 << port;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brr_reset_only_writes_one_bit() {
+        let mut brr = BRR(0b1 << 2);
+
+        brr.reset(7);
+        assert_eq!(brr.0, 0b1 << 7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_brr_reset_panics_when_port_is_out_of_bounds() {
+        BRR(0).reset(16);
+    }
+}
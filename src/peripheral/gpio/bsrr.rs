@@ -36,6 +36,12 @@ impl BSRR {
         }
         self.0 |= 0b1 << (port + BSRR_RESET_OFFSET);
     }
+
+    /// Set every port in `set` high and every port in `reset` low in a single write. If a port is
+    /// in both, it is set high.
+    pub fn write(&mut self, set: u16, reset: u16) {
+        self.0 = (reset as u32) << BSRR_RESET_OFFSET | set as u32;
+    }
 }
 
 #[cfg(test)]
@@ -77,6 +83,13 @@ mod tests {
         assert_eq!(bsrr.0, 0x8000_0000);
     }
 
+    #[test]
+    fn test_bsrr_write_sets_and_resets_in_one_write() {
+        let mut bsrr = BSRR(0);
+        bsrr.write(0b0101, 0b1010);
+        assert_eq!(bsrr.0, 0b1010 << 16 | 0b0101);
+    }
+
     #[test]
     #[should_panic]
     fn test_bsrr_reset_panics_when_port_is_out_of_bounds() {
@@ -37,10 +37,16 @@ pub const PUPD_NEITHER: u32 = 0b00;
 pub const PUPD_UP: u32 = 0b01;
 pub const PUPD_DOWN: u32 = 0b10;
 
+pub const IDR_OFFSET: u32 = 0x10;
+pub const ODR_OFFSET: u32 = 0x14;
+pub const DATA_MASK: u32 = 0xFFFF;
 
 pub const BSRR_OFFSET: u32 = 0x18;
 pub const BSRR_RESET_OFFSET: u8 = 16;
 
+pub const LCKR_OFFSET: u32 = 0x1C;
+pub const LCKR_LCKK: u32 = 0b1 << 16;
+
 pub const AFRL_OFFSET: u32 = 0x20;
 pub const AFR_MASK: u32 = 0b1111;
 pub const AF0: u32 = 0b0000;
@@ -54,6 +60,8 @@ pub const AF7: u32 = 0b0111;
 
 pub const AFRH_OFFSET: u32 = 0x24;
 
+pub const BRR_OFFSET: u32 = 0x28;
+
 
 pub const MODER_OFFSET: u32 = 0x00;
 pub const MODE_MASK: u32 = 0b11;
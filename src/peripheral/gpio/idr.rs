@@ -0,0 +1,64 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use super::defs::*;
+
+/// The input data register, holds the level read on each pin.
+#[derive(Copy, Clone, Debug)]
+pub struct IDR(u32);
+
+impl IDR {
+    /// Return true if the specified port reads high, port must be a value between [0..15] or the
+    /// kernel will panic.
+    pub fn get(&self, port: u8) -> bool {
+        if port > 15 {
+            panic!("IDR::get - specified port must be between [0..15]!");
+        }
+        self.0 & (0b1 << port) != 0
+    }
+
+    /// Get the level of every pin in the group, one bit per port.
+    pub fn get_all(&self) -> u16 {
+        (self.0 & DATA_MASK) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idr_get() {
+        let idr = IDR(0b1 << 5);
+
+        assert_eq!(idr.get(5), true);
+        assert_eq!(idr.get(4), false);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_idr_get_panics_when_port_is_out_of_bounds() {
+        IDR(0).get(16);
+    }
+
+    #[test]
+    fn test_idr_get_all_ignores_reserved_bits() {
+        let idr = IDR(0xFFFF_8001);
+
+        assert_eq!(idr.get_all(), 0x8001);
+    }
+}
@@ -0,0 +1,94 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use super::defs::*;
+
+/// The lock register, freezes the configuration of pins until the next reset.
+///
+/// Locking takes a write sequence on the LCKK bit: 1, 0, 1, with the same pins each time,
+/// followed by a read. If the sequence was followed the LCKK bit reads back as 1 and the pins
+/// stay locked until the next reset.
+#[derive(Copy, Clone, Debug)]
+pub struct LCKR(u32);
+
+impl LCKR {
+    /// A register value with no key and no ports set.
+    pub fn new() -> Self {
+        LCKR(0)
+    }
+
+    /// Write one step of the lock sequence for the ports in `mask`.
+    pub fn write_sequence(&mut self, key: bool, mask: u16) {
+        let key = if key { LCKR_LCKK } else { 0 };
+        self.0 = key | mask as u32;
+    }
+
+    /// Return true if the lock key is active, meaning the configuration is frozen.
+    pub fn key_is_set(&self) -> bool {
+        self.0 & LCKR_LCKK != 0
+    }
+
+    /// Return true if the specified port's configuration is frozen until reset, port must be a
+    /// value between [0..15] or the kernel will panic.
+    pub fn is_locked(&self, port: u8) -> bool {
+        if port > 15 {
+            panic!("LCKR::is_locked - specified port must be between [0..15]!");
+        }
+        self.key_is_set() && self.0 & (0b1 << port) != 0
+    }
+
+    /// Return true if the lock key is active and every port in `mask` is frozen.
+    pub fn all_locked(&self, mask: u16) -> bool {
+        self.key_is_set() && self.0 as u16 & mask == mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lckr_write_sequence() {
+        let mut lckr = LCKR(0);
+
+        lckr.write_sequence(true, 0b1001);
+        assert_eq!(lckr.0, 0b1 << 16 | 0b1001);
+
+        lckr.write_sequence(false, 0b1001);
+        assert_eq!(lckr.0, 0b1001);
+    }
+
+    #[test]
+    fn test_lckr_is_locked_needs_key() {
+        assert_eq!(LCKR(0b1 << 3).is_locked(3), false);
+        assert_eq!(LCKR(0b1 << 16 | 0b1 << 3).is_locked(3), true);
+        assert_eq!(LCKR(0b1 << 16 | 0b1 << 3).is_locked(4), false);
+    }
+
+    #[test]
+    fn test_lckr_all_locked_needs_key_and_every_port() {
+        assert_eq!(LCKR(0b1001).all_locked(0b1001), false);
+        assert_eq!(LCKR(0b1 << 16 | 0b1001).all_locked(0b1001), true);
+        assert_eq!(LCKR(0b1 << 16 | 0b0001).all_locked(0b1001), false);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_lckr_is_locked_panics_when_port_is_out_of_bounds() {
+        LCKR(0).is_locked(16);
+    }
+}
@@ -21,12 +21,21 @@ mod port;
 mod moder;
 mod otyper;
 mod bsrr;
+mod idr;
+mod odr;
+mod brr;
+mod lckr;
 mod ospeedr;
 mod pupdr;
 mod afr;
+mod pins;
+mod pin;
+mod pin_id;
+pub mod af;
 mod defs;
 
 use core::ops::{Deref, DerefMut};
+use core::ptr;
 use volatile::Volatile;
 use super::rcc;
 use self::defs::*;
@@ -37,12 +46,22 @@ pub use self::otyper::Type;
 pub use self::ospeedr::Speed;
 pub use self::pupdr::Pull;
 pub use self::afr::AlternateFunction;
+pub use self::pins::{GpioGroup, Parts, GroupPorts, Pins};
+pub use self::pin::{Pin, Input, Output, Alternate, Analog, Unknown, Erased};
+pub use self::pin::{Floating, PullUp, PullDown, PushPull, OpenDrain};
+pub use self::pin::{AF0, AF1, AF2, AF3, AF4, AF5, AF6, AF7};
+pub use self::pin::{PullMode, OutputType, AlternateFunctionMode};
+pub use self::pin_id::*;
 
 use self::moder::MODER;
 use self::otyper::OTYPER;
 use self::ospeedr::OSPEEDR;
 use self::pupdr::PUPDR;
 use self::bsrr::BSRR;
+use self::idr::IDR;
+use self::odr::ODR;
+use self::brr::BRR;
+use self::lckr::LCKR;
 use self::afr::{AFRL, AFRH};
 
 /// An IO group containing up to 16 pins. For some reason, the datasheet shows the memory
@@ -59,6 +78,16 @@ pub enum Group {
     F,
 }
 
+impl Group {
+    /// Return true if the pins in this group can have their configuration locked.
+    pub fn can_lock(&self) -> bool {
+        match *self {
+            Group::A | Group::B => true,
+            Group::C | Group::F => false,
+        }
+    }
+}
+
 /// A GPIO contains the base address for a memory mapped GPIO group associated with it.
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
@@ -68,13 +97,13 @@ pub struct RawGPIO {
     otyper: OTYPER,
     ospeedr: OSPEEDR,
     pupdr: PUPDR,
-    idr: u32,
-    odr: u32,
+    idr: IDR,
+    odr: ODR,
     bsrr: BSRR,
-    lckr: u32,
+    lckr: LCKR,
     afrl: AFRL,
     afrh: AFRH,
-    brr: u32,
+    brr: BRR,
 }
 
 /// Creates struct for accessing the GPIO groups.
@@ -85,6 +114,7 @@ pub struct RawGPIO {
 pub struct GPIO(Volatile<RawGPIO>);
 
 impl GPIO {
+    #[cfg(not(test))]
     fn group(group: Group) -> GPIO {
         match group {
             Group::A => GPIO::new(GROUPA_ADDR),
@@ -94,6 +124,24 @@ impl GPIO {
         }
     }
 
+    // Tests get their own copy of the registers for each thread, so they can check what was
+    // written to them without the tests running alongside interfering
+    #[cfg(test)]
+    fn group(group: Group) -> GPIO {
+        use std::cell::UnsafeCell;
+        use core::mem;
+
+        thread_local! {
+            static GROUPS: UnsafeCell<[RawGPIO; 4]> = UnsafeCell::new(unsafe { mem::zeroed() })
+        }
+
+        let index = group as isize;
+        GROUPS.with(|groups| {
+            // UNSAFE: The registers live as long as the test thread
+            GPIO::new(unsafe { (*groups.get()).as_mut_ptr().offset(index) } as *const u32)
+        })
+    }
+
     fn new(mem_addr: *const u32) -> GPIO {
         unsafe {
             GPIO(Volatile::new(mem_addr as *const _))
@@ -128,7 +176,8 @@ impl RawGPIO {
     ///   GPIO::enable(Group::B); // Enable IO group B (LED is pb3)
     /// ```
     pub fn enable(group: Group) {
-        let mut rcc = rcc::rcc();
+        // UNSAFE: Only the enable bit for this group is changed
+        let mut rcc = unsafe { rcc::rcc() };
 
         // Get the register bit that should be set to enable this group
         let io_group = match group {
@@ -191,7 +240,73 @@ impl RawGPIO {
     ///
     /// Port must be a value between [0..15] or the kernel will panic.
     fn reset_bit(&mut self, port: u8) {
-        self.bsrr.reset(port);
+        self.brr.reset(port);
+    }
+
+    /// Return true if the specified port reads high.
+    ///
+    /// # Panics
+    ///
+    /// Port must be a value between [0..15] or the kernel will panic.
+    fn get_input_bit(&self, port: u8) -> bool {
+        self.idr.get(port)
+    }
+
+    /// Return true if the specified port is being driven high.
+    ///
+    /// # Panics
+    ///
+    /// Port must be a value between [0..15] or the kernel will panic.
+    fn get_output_bit(&self, port: u8) -> bool {
+        self.odr.get(port)
+    }
+
+    /// Drive the ports in `mask` to the matching bits of `value` in a single write, leaving the
+    /// other ports alone.
+    pub fn write_masked(&mut self, mask: u16, value: u16) {
+        self.bsrr.write(value & mask, !value & mask);
+    }
+
+    /// Read the level of every pin in the group, one bit per port.
+    pub fn read_all(&self) -> u16 {
+        self.idr.get_all()
+    }
+
+    /// Read the level every pin in the group is being driven to, one bit per port.
+    pub fn read_output(&self) -> u16 {
+        self.odr.get_all()
+    }
+
+    /// Freeze the configuration of the ports in `mask` until the next reset, returning true if
+    /// the lock took effect.
+    ///
+    /// Only groups A and B can be locked.
+    pub fn lock_pins(&mut self, mask: u16) -> bool {
+        let lckr = &mut self.lckr as *mut LCKR;
+        let mut locking = LCKR::new();
+        let mut unlocking = LCKR::new();
+        locking.write_sequence(true, mask);
+        unlocking.write_sequence(false, mask);
+
+        // UNSAFE: The register belongs to this group. The sequence must be written exactly, any
+        // other write in between aborts it, so every access has to be volatile
+        unsafe {
+            ptr::write_volatile(lckr, locking);
+            ptr::write_volatile(lckr, unlocking);
+            ptr::write_volatile(lckr, locking);
+            // The first read completes the sequence, the second reports whether it worked
+            let _ = ptr::read_volatile(lckr);
+            ptr::read_volatile(lckr).all_locked(mask)
+        }
+    }
+
+    /// Return true if the specified port's configuration is frozen until reset.
+    ///
+    /// # Panics
+    ///
+    /// Port must be a value between [0..15] or the kernel will panic.
+    pub fn pin_is_locked(&self, port: u8) -> bool {
+        self.lckr.is_locked(port)
     }
 
     /// Sets the port speed for the GPIO pin.
@@ -0,0 +1,396 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module provides GPIO pins that keep track of their mode in their type, so a pin can only
+//! be used in ways that make sense for how it's configured.
+//!
+//! Pins that come from splitting a group also keep track of which pin they are in their type, so
+//! drivers can ask for a specific pin (see the `af` module).
+//!
+//! Example Usage:
+//! ```
+//!   let pins = peripherals.gpiob.split().pins;
+//!   let mut led = pins.p4.unwrap().into_push_pull_output();
+//!   led.set_high(); // Light's on!
+//! ```
+
+use core::marker::PhantomData;
+use super::{Port, Mode, Type, Speed, Pull, AlternateFunction};
+use super::pin_id::PinId;
+
+/// Input mode, with the pull behavior `PULL`.
+pub struct Input<PULL>(PhantomData<PULL>);
+/// Output mode, with the output type `TYPE`.
+pub struct Output<TYPE>(PhantomData<TYPE>);
+/// Alternate function mode, with the function `AF`.
+pub struct Alternate<AF>(PhantomData<AF>);
+/// Analog mode.
+pub struct Analog;
+/// Whatever mode the pin was left in, it has to be converted before it can be used.
+pub struct Unknown;
+
+/// A pin whose identity is only known at runtime.
+pub struct Erased;
+
+/// Input with no pull resistor.
+pub struct Floating;
+/// Input pulled toward high voltage.
+pub struct PullUp;
+/// Input pulled toward low voltage.
+pub struct PullDown;
+
+/// Output actively driven high and low.
+pub struct PushPull;
+/// Output only driven low.
+pub struct OpenDrain;
+
+/// Alternate function 0
+pub struct AF0;
+/// Alternate function 1
+pub struct AF1;
+/// Alternate function 2
+pub struct AF2;
+/// Alternate function 3
+pub struct AF3;
+/// Alternate function 4
+pub struct AF4;
+/// Alternate function 5
+pub struct AF5;
+/// Alternate function 6
+pub struct AF6;
+/// Alternate function 7
+pub struct AF7;
+
+/// An input pull behavior that can be encoded in a pin's type.
+pub trait PullMode {
+    /// The pull behavior this type represents.
+    fn pull() -> Pull;
+}
+
+/// An output type that can be encoded in a pin's type.
+pub trait OutputType {
+    /// The output type this type represents.
+    fn output_type() -> Type;
+}
+
+/// An alternate function that can be encoded in a pin's type.
+pub trait AlternateFunctionMode {
+    /// The alternate function this type represents.
+    fn function() -> AlternateFunction;
+}
+
+impl PullMode for Floating {
+    fn pull() -> Pull { Pull::Neither }
+}
+
+impl PullMode for PullUp {
+    fn pull() -> Pull { Pull::Up }
+}
+
+impl PullMode for PullDown {
+    fn pull() -> Pull { Pull::Down }
+}
+
+impl OutputType for PushPull {
+    fn output_type() -> Type { Type::PushPull }
+}
+
+impl OutputType for OpenDrain {
+    fn output_type() -> Type { Type::OpenDrain }
+}
+
+impl AlternateFunctionMode for AF0 {
+    fn function() -> AlternateFunction { AlternateFunction::Zero }
+}
+
+impl AlternateFunctionMode for AF1 {
+    fn function() -> AlternateFunction { AlternateFunction::One }
+}
+
+impl AlternateFunctionMode for AF2 {
+    fn function() -> AlternateFunction { AlternateFunction::Two }
+}
+
+impl AlternateFunctionMode for AF3 {
+    fn function() -> AlternateFunction { AlternateFunction::Three }
+}
+
+impl AlternateFunctionMode for AF4 {
+    fn function() -> AlternateFunction { AlternateFunction::Four }
+}
+
+impl AlternateFunctionMode for AF5 {
+    fn function() -> AlternateFunction { AlternateFunction::Five }
+}
+
+impl AlternateFunctionMode for AF6 {
+    fn function() -> AlternateFunction { AlternateFunction::Six }
+}
+
+impl AlternateFunctionMode for AF7 {
+    fn function() -> AlternateFunction { AlternateFunction::Seven }
+}
+
+/// A GPIO pin configured in `MODE`. `ID` is the specific pin (e.g. `PA2`), or `Erased` if the
+/// pin was created from a `Port`.
+pub struct Pin<MODE, ID = Erased> {
+    port: Port,
+    _mode: PhantomData<MODE>,
+    _id: PhantomData<ID>,
+}
+
+impl Pin<Input<Floating>> {
+    /// Take ownership of a port, configuring it as a floating input.
+    pub fn new(port: Port) -> Self {
+        configure_input(port, Pull::Neither)
+    }
+}
+
+impl<ID: PinId> Pin<Unknown, ID> {
+    /// Create the pin `ID` without configuring it.
+    ///
+    /// This is unsafe because nothing stops the same pin from being created twice, pins should
+    /// come from splitting a `GpioGroup`.
+    #[doc(hidden)]
+    pub unsafe fn new_unchecked() -> Self {
+        Pin::from_port(Port::new(ID::port(), ID::group()))
+    }
+}
+
+impl<MODE, ID> Pin<MODE, ID> {
+    fn from_port(port: Port) -> Self {
+        Pin {
+            port: port,
+            _mode: PhantomData,
+            _id: PhantomData,
+        }
+    }
+
+    /// Configure the pin as an input with no pull resistor.
+    pub fn into_floating_input(self) -> Pin<Input<Floating>, ID> {
+        configure_input(self.port, Floating::pull())
+    }
+
+    /// Configure the pin as an input pulled toward high voltage.
+    pub fn into_pull_up_input(self) -> Pin<Input<PullUp>, ID> {
+        configure_input(self.port, PullUp::pull())
+    }
+
+    /// Configure the pin as an input pulled toward low voltage.
+    pub fn into_pull_down_input(self) -> Pin<Input<PullDown>, ID> {
+        configure_input(self.port, PullDown::pull())
+    }
+
+    /// Configure the pin as an output that drives both high and low.
+    pub fn into_push_pull_output(self) -> Pin<Output<PushPull>, ID> {
+        self.into_output()
+    }
+
+    /// Configure the pin as an output that only drives low.
+    pub fn into_open_drain_output(self) -> Pin<Output<OpenDrain>, ID> {
+        self.into_output()
+    }
+
+    /// Configure the pin as an output of type `TYPE`.
+    pub fn into_output<TYPE: OutputType>(self) -> Pin<Output<TYPE>, ID> {
+        let mut port = self.port;
+        port.set_type(TYPE::output_type());
+        port.set_pull(Pull::Neither);
+        port.set_mode(Mode::Output);
+        Pin::from_port(port)
+    }
+
+    /// Connect the pin to alternate function `AF`.
+    ///
+    /// Example Usage:
+    /// ```
+    ///   let tx = pins.p9.unwrap().into_alternate::<AF1>(); // USART1 TX
+    /// ```
+    pub fn into_alternate<AF: AlternateFunctionMode>(self) -> Pin<Alternate<AF>, ID> {
+        let mut port = self.port;
+        // Select the function before switching modes so the pin never outputs the wrong one
+        port.set_function(AF::function());
+        port.set_mode(Mode::Alternate);
+        Pin::from_port(port)
+    }
+
+    /// Configure the pin for analog signals.
+    pub fn into_analog(self) -> Pin<Analog, ID> {
+        let mut port = self.port;
+        port.set_pull(Pull::Neither);
+        port.set_mode(Mode::Analog);
+        Pin::from_port(port)
+    }
+
+    /// Forget which pin this is, so pins of different identities can be stored together.
+    pub fn erase(self) -> Pin<MODE, Erased> {
+        Pin::from_port(self.port)
+    }
+
+    /// Give up the type state and get the underlying port back.
+    pub fn into_port(self) -> Port {
+        self.port
+    }
+}
+
+fn configure_input<PULL, ID>(mut port: Port, pull: Pull) -> Pin<Input<PULL>, ID> {
+    port.set_mode(Mode::Input);
+    port.set_pull(pull);
+    Pin::from_port(port)
+}
+
+impl<PULL, ID> Pin<Input<PULL>, ID> {
+    /// Return true if the pin reads high.
+    pub fn is_high(&self) -> bool {
+        self.port.is_high()
+    }
+
+    /// Return true if the pin reads low.
+    pub fn is_low(&self) -> bool {
+        self.port.is_low()
+    }
+}
+
+impl<TYPE, ID> Pin<Output<TYPE>, ID> {
+    /// Set the pin high.
+    pub fn set_high(&mut self) {
+        self.port.set();
+    }
+
+    /// Set the pin low.
+    pub fn set_low(&mut self) {
+        self.port.reset();
+    }
+
+    /// Return true if the pin is being driven high.
+    pub fn is_set_high(&self) -> bool {
+        self.port.is_set_high()
+    }
+
+    /// Return true if the pin is being driven low.
+    pub fn is_set_low(&self) -> bool {
+        self.port.is_set_low()
+    }
+
+    /// Drive the pin to the opposite level.
+    pub fn toggle(&mut self) {
+        self.port.toggle();
+    }
+
+    /// Set the pin speed.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.port.set_speed(speed);
+    }
+}
+
+impl<AF, ID> Pin<Alternate<AF>, ID> {
+    /// Set the pin speed.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.port.set_speed(speed);
+    }
+
+    /// Set the output type used by the alternate function.
+    pub fn set_type(&mut self, p_type: Type) {
+        self.port.set_type(p_type);
+    }
+
+    /// Set the behavior of the pin when it is not driven.
+    pub fn set_pull(&mut self, pull: Pull) {
+        self.port.set_pull(pull);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::pin_id::{PA2, PA9, PB4};
+
+    fn pa2() -> Pin<Unknown, PA2> {
+        unsafe { Pin::new_unchecked() }
+    }
+
+    #[test]
+    fn test_into_push_pull_output() {
+        let pin = pa2().into_pull_down_input().into_push_pull_output();
+        let port = pin.into_port();
+
+        assert_eq!(port.get_mode(), Mode::Output);
+        assert_eq!(port.get_type(), Type::PushPull);
+        assert_eq!(port.get_pull(), Pull::Neither);
+    }
+
+    #[test]
+    fn test_into_open_drain_output() {
+        let port = pa2().into_open_drain_output().into_port();
+
+        assert_eq!(port.get_mode(), Mode::Output);
+        assert_eq!(port.get_type(), Type::OpenDrain);
+    }
+
+    #[test]
+    fn test_into_pull_up_input() {
+        let port = pa2().into_pull_up_input().into_port();
+
+        assert_eq!(port.get_mode(), Mode::Input);
+        assert_eq!(port.get_pull(), Pull::Up);
+    }
+
+    #[test]
+    fn test_into_floating_input_clears_pull() {
+        let port = pa2().into_pull_up_input().into_floating_input().into_port();
+
+        assert_eq!(port.get_mode(), Mode::Input);
+        assert_eq!(port.get_pull(), Pull::Neither);
+    }
+
+    #[test]
+    fn test_into_alternate_low_pin() {
+        let port = pa2().into_alternate::<AF1>().into_port();
+
+        assert_eq!(port.get_mode(), Mode::Alternate);
+        assert_eq!(port.get_function(), AlternateFunction::One);
+    }
+
+    #[test]
+    fn test_into_alternate_high_pin() {
+        let pin: Pin<Unknown, PA9> = unsafe { Pin::new_unchecked() };
+        let port = pin.into_alternate::<AF4>().into_port();
+
+        assert_eq!(port.get_mode(), Mode::Alternate);
+        assert_eq!(port.get_function(), AlternateFunction::Four);
+    }
+
+    #[test]
+    fn test_into_analog() {
+        let port = pa2().into_pull_up_input().into_analog().into_port();
+
+        assert_eq!(port.get_mode(), Mode::Analog);
+        assert_eq!(port.get_pull(), Pull::Neither);
+    }
+
+    #[test]
+    fn test_conversion_leaves_other_pins_alone() {
+        let pb4: Pin<Unknown, PB4> = unsafe { Pin::new_unchecked() };
+        let pb4 = pb4.into_pull_up_input().into_port();
+        pa2().into_push_pull_output();
+
+        assert_eq!(pb4.get_mode(), Mode::Input);
+        assert_eq!(pb4.get_pull(), Pull::Up);
+        let pa9: Pin<Unknown, PA9> = unsafe { Pin::new_unchecked() };
+        assert_eq!(pa9.into_port().get_mode(), Mode::Input);
+    }
+}
@@ -0,0 +1,140 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module defines types that name each GPIO pin, so a pin's identity can be part of its type.
+
+use core::marker::PhantomData;
+use super::Group;
+
+/// A GPIO group known at compile time.
+pub trait GroupId {
+    /// The group this type represents.
+    fn group() -> Group;
+}
+
+/// A pin number known at compile time.
+pub trait PinNumber {
+    /// The pin number this type represents, in the range [0..15].
+    fn number() -> u8;
+}
+
+/// A specific GPIO pin known at compile time.
+pub trait PinId {
+    /// The group the pin belongs to.
+    fn group() -> Group;
+    /// The pin's number within its group.
+    fn port() -> u8;
+}
+
+/// GPIO Group A
+pub struct GroupA;
+/// GPIO Group B
+pub struct GroupB;
+/// GPIO Group C
+pub struct GroupC;
+/// GPIO Group F
+pub struct GroupF;
+
+impl GroupId for GroupA {
+    fn group() -> Group { Group::A }
+}
+
+impl GroupId for GroupB {
+    fn group() -> Group { Group::B }
+}
+
+impl GroupId for GroupC {
+    fn group() -> Group { Group::C }
+}
+
+impl GroupId for GroupF {
+    fn group() -> Group { Group::F }
+}
+
+/// Pin `N` of group `G`.
+pub struct Id<G, N>(PhantomData<G>, PhantomData<N>);
+
+impl<G: GroupId, N: PinNumber> PinId for Id<G, N> {
+    fn group() -> Group { G::group() }
+    fn port() -> u8 { N::number() }
+}
+
+macro_rules! pin_numbers {
+    ($($name:ident => $number:expr,)*) => {
+        $(
+            #[allow(missing_docs)]
+            pub struct $name;
+
+            impl PinNumber for $name {
+                fn number() -> u8 { $number }
+            }
+        )*
+    }
+}
+
+macro_rules! pin_aliases {
+    ($group:ident: $($name:ident => $number:ident,)*) => {
+        $(
+            #[allow(missing_docs)]
+            pub type $name = Id<$group, $number>;
+        )*
+    }
+}
+
+pin_numbers! {
+    N0 => 0, N1 => 1, N2 => 2, N3 => 3, N4 => 4, N5 => 5, N6 => 6, N7 => 7,
+    N8 => 8, N9 => 9, N10 => 10, N11 => 11, N12 => 12, N13 => 13, N14 => 14, N15 => 15,
+}
+
+pin_aliases! { GroupA:
+    PA0 => N0, PA1 => N1, PA2 => N2, PA3 => N3, PA4 => N4, PA5 => N5, PA6 => N6, PA7 => N7,
+    PA8 => N8, PA9 => N9, PA10 => N10, PA11 => N11, PA12 => N12, PA13 => N13, PA14 => N14,
+    PA15 => N15,
+}
+
+pin_aliases! { GroupB:
+    PB0 => N0, PB1 => N1, PB2 => N2, PB3 => N3, PB4 => N4, PB5 => N5, PB6 => N6, PB7 => N7,
+    PB8 => N8, PB9 => N9, PB10 => N10, PB11 => N11, PB12 => N12, PB13 => N13, PB14 => N14,
+    PB15 => N15,
+}
+
+pin_aliases! { GroupC:
+    PC0 => N0, PC1 => N1, PC2 => N2, PC3 => N3, PC4 => N4, PC5 => N5, PC6 => N6, PC7 => N7,
+    PC8 => N8, PC9 => N9, PC10 => N10, PC11 => N11, PC12 => N12, PC13 => N13, PC14 => N14,
+    PC15 => N15,
+}
+
+pin_aliases! { GroupF:
+    PF0 => N0, PF1 => N1, PF2 => N2, PF3 => N3, PF4 => N4, PF5 => N5, PF6 => N6, PF7 => N7,
+    PF8 => N8, PF9 => N9, PF10 => N10, PF11 => N11, PF12 => N12, PF13 => N13, PF14 => N14,
+    PF15 => N15,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_id_maps_to_group_and_port() {
+        assert_eq!(<PA2 as PinId>::port(), 2);
+        assert_eq!(<PB15 as PinId>::port(), 15);
+        match <PF0 as PinId>::group() {
+            Group::F => {},
+            _ => panic!("PF0 should be in group F"),
+        }
+    }
+}
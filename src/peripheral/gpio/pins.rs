@@ -0,0 +1,179 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module splits a GPIO group into individually owned pins, and a handle for driving
+//! several of them at once.
+
+use core::marker::PhantomData;
+use super::{GPIO, Group};
+use super::pin::{Pin, Unknown};
+use super::pin_id::*;
+use board;
+
+/// Ownership of a whole GPIO group `G`, handed out once by `Peripherals::take`.
+///
+/// The group is split into its pins with `split`, after which each pin can be given to a
+/// different driver without them stepping on each other.
+pub struct GpioGroup<G> {
+    _group: PhantomData<G>,
+}
+
+impl<G: GroupId> GpioGroup<G> {
+    #[doc(hidden)]
+    pub unsafe fn new() -> Self {
+        GpioGroup {
+            _group: PhantomData,
+        }
+    }
+
+    /// Enable the group and split it into a handle for the whole group and its 16 pins. The pins
+    /// are left in whatever mode they were in, so each has to be converted before it's used.
+    ///
+    /// The pins the board reserves for its console, LEDs and button are `None`, see
+    /// `board::reserved_ports`.
+    pub fn split(self) -> Parts<G> {
+        GPIO::enable(G::group());
+        let reserved = board::reserved_ports(G::group());
+        // UNSAFE: The group is consumed, so each of its pins is only created once
+        unsafe {
+            Parts {
+                group: GroupPorts {
+                    group: G::group(),
+                    reserved: reserved,
+                    _group: PhantomData,
+                },
+                pins: Pins {
+                    p0: free_pin(reserved),
+                    p1: free_pin(reserved),
+                    p2: free_pin(reserved),
+                    p3: free_pin(reserved),
+                    p4: free_pin(reserved),
+                    p5: free_pin(reserved),
+                    p6: free_pin(reserved),
+                    p7: free_pin(reserved),
+                    p8: free_pin(reserved),
+                    p9: free_pin(reserved),
+                    p10: free_pin(reserved),
+                    p11: free_pin(reserved),
+                    p12: free_pin(reserved),
+                    p13: free_pin(reserved),
+                    p14: free_pin(reserved),
+                    p15: free_pin(reserved),
+                },
+            }
+        }
+    }
+}
+
+// Create the pin unless the board has reserved it.
+unsafe fn free_pin<ID: PinId>(reserved: u16) -> Option<Pin<Unknown, ID>> {
+    if reserved & (0b1 << ID::port()) == 0 {
+        Some(Pin::new_unchecked())
+    }
+    else {
+        None
+    }
+}
+
+/// A GPIO group split by `GpioGroup::split`.
+pub struct Parts<G> {
+    /// Operations on the group as a whole.
+    pub group: GroupPorts<G>,
+    /// The group's pins.
+    pub pins: Pins<G>,
+}
+
+/// Operations that act on several ports of GPIO group `G` at once.
+///
+/// These work alongside the split pins, so the ports making up a parallel bus can be configured
+/// as outputs through their pins and then driven together. The ports the board has reserved are
+/// never touched.
+pub struct GroupPorts<G> {
+    group: Group,
+    reserved: u16,
+    _group: PhantomData<G>,
+}
+
+impl<G: GroupId> GroupPorts<G> {
+    /// Drive the ports in `mask` to the matching bits of `value` in a single write, leaving the
+    /// other ports alone. This is useful for driving a parallel bus.
+    ///
+    /// Example Usage:
+    /// ```
+    ///   let gpiob = peripherals.gpiob.split();
+    ///   let mut bus = gpiob.group;
+    ///   let d0 = gpiob.pins.p0.unwrap().into_push_pull_output();
+    ///   // ... PB1-PB7 the same way
+    ///   bus.write_masked(0x00FF, byte as u16); // Put a byte on PB0-PB7
+    /// ```
+    pub fn write_masked(&mut self, mask: u16, value: u16) {
+        let mut gpio = GPIO::group(self.group);
+        gpio.write_masked(mask & !self.reserved, value);
+    }
+
+    /// Read the level of every pin in the group, one bit per port.
+    pub fn read_all(&self) -> u16 {
+        let gpio = GPIO::group(self.group);
+        gpio.read_all()
+    }
+
+    /// Freeze the configuration of the ports in `mask` until the next reset, returning true if
+    /// the lock took effect. Only groups A and B can be locked.
+    pub fn lock_pins(&mut self, mask: u16) -> bool {
+        if !self.group.can_lock() {
+            return false;
+        }
+        let mut gpio = GPIO::group(self.group);
+        gpio.lock_pins(mask & !self.reserved)
+    }
+}
+
+/// The pins of GPIO group `G`, each owned separately. Pins the board has reserved are `None`.
+#[allow(missing_docs)]
+pub struct Pins<G> {
+    pub p0: Option<Pin<Unknown, Id<G, N0>>>,
+    pub p1: Option<Pin<Unknown, Id<G, N1>>>,
+    pub p2: Option<Pin<Unknown, Id<G, N2>>>,
+    pub p3: Option<Pin<Unknown, Id<G, N3>>>,
+    pub p4: Option<Pin<Unknown, Id<G, N4>>>,
+    pub p5: Option<Pin<Unknown, Id<G, N5>>>,
+    pub p6: Option<Pin<Unknown, Id<G, N6>>>,
+    pub p7: Option<Pin<Unknown, Id<G, N7>>>,
+    pub p8: Option<Pin<Unknown, Id<G, N8>>>,
+    pub p9: Option<Pin<Unknown, Id<G, N9>>>,
+    pub p10: Option<Pin<Unknown, Id<G, N10>>>,
+    pub p11: Option<Pin<Unknown, Id<G, N11>>>,
+    pub p12: Option<Pin<Unknown, Id<G, N12>>>,
+    pub p13: Option<Pin<Unknown, Id<G, N13>>>,
+    pub p14: Option<Pin<Unknown, Id<G, N14>>>,
+    pub p15: Option<Pin<Unknown, Id<G, N15>>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_pins_are_left_out() {
+        let reserved = 0b1 << 2 | 0b1 << 15;
+        unsafe {
+            assert!(free_pin::<PA2>(reserved).is_none());
+            assert!(free_pin::<PA15>(reserved).is_none());
+            assert!(free_pin::<PA3>(reserved).is_some());
+        }
+    }
+}
@@ -23,7 +23,7 @@ use super::{GPIO, Mode, Group, Type, Speed, Pull, AlternateFunction};
 ///
 /// Example Usage:
 /// ```
-///   let mut port = Port::new(3, Group::B); // The port to the user LED
+///   let mut port = unsafe { Port::new(3, Group::B) }; // The port to the user LED
 ///   port.set_mode(Mode::Output);
 ///   port.set_type(Type::PushPull);
 ///   port.set(); // Light's green!
@@ -36,7 +36,11 @@ pub struct Port {
 impl Port {
     /// Create a new port for the associated group. Ports are NOT thread safe, if you must ensure
     /// an atomic set of operations on a port, use some kind of synchronization tool.
-    pub fn new(port: u8, group: Group) -> Port {
+    ///
+    /// This is unsafe because nothing stops the same port from being created twice, application
+    /// code should get its pins from `Peripherals::take` instead.
+    #[doc(hidden)]
+    pub unsafe fn new(port: u8, group: Group) -> Port {
         if port > 15 {
             panic!("Port::new - port must be a value between 0..15");
         }
@@ -117,4 +121,55 @@ impl Port {
         let mut gpio = GPIO::group(self.group);
         gpio.reset_bit(self.port);
     }
+
+    /// Return true if the pin reads high.
+    pub fn is_high(&self) -> bool {
+        let gpio = GPIO::group(self.group);
+        gpio.get_input_bit(self.port)
+    }
+
+    /// Return true if the pin reads low.
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+
+    /// Return true if the pin is being driven high.
+    pub fn is_set_high(&self) -> bool {
+        let gpio = GPIO::group(self.group);
+        gpio.get_output_bit(self.port)
+    }
+
+    /// Return true if the pin is being driven low.
+    pub fn is_set_low(&self) -> bool {
+        !self.is_set_high()
+    }
+
+    /// Drive the pin to the opposite level.
+    pub fn toggle(&mut self) {
+        if self.is_set_high() {
+            self.reset();
+        }
+        else {
+            self.set();
+        }
+    }
+
+    /// Freeze the port's configuration until the next reset, returning true if the lock took
+    /// effect. Only ports in groups A and B can be locked.
+    pub fn lock(&mut self) -> bool {
+        if !self.group.can_lock() {
+            return false;
+        }
+        let mut gpio = GPIO::group(self.group);
+        gpio.lock_pins(0b1 << self.port)
+    }
+
+    /// Return true if the port's configuration is frozen until the next reset.
+    pub fn is_locked(&self) -> bool {
+        if !self.group.can_lock() {
+            return false;
+        }
+        let gpio = GPIO::group(self.group);
+        gpio.pin_is_locked(self.port)
+    }
 }
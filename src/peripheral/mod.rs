@@ -17,7 +17,11 @@
 
 //! This module handles the memory mapped peripherals that are a part of the Cortex-M0. Submodules
 //! will handle the more specific details of each peripheral.
+mod peripherals;
+
 pub mod rcc;
+pub mod flash;
+pub mod crs;
 pub mod gpio;
 pub mod systick;
 #[cfg(feature="dma")]
@@ -25,6 +29,8 @@ pub mod dma;
 #[cfg(feature="serial")]
 pub mod usart;
 
+pub use self::peripherals::Peripherals;
+
 #[macro_export]
 macro_rules! pad_field {
     ($name:ident[$N:expr]) => {
@@ -0,0 +1,99 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module hands out the peripherals to the application, making sure each one only has a
+//! single owner.
+
+use altos_core::sync::CriticalSection;
+use interrupt::{self, Nvic};
+use super::{rcc, flash, crs, gpio};
+
+static mut TAKEN: bool = false;
+
+/// All of the peripherals available to the application.
+///
+/// The peripherals the kernel drives itself are left out: the SysTick and System Control Block
+/// are used for scheduling. The Usarts are left out as well, each one is owned by whoever opens
+/// it with `usart::open` or `io::open`, and with the `serial` feature the kernel opens the
+/// board's console Usart. The pins the board uses for its console, LEDs and button are left out
+/// when their group is split, the LEDs and button are taken through `board::take_led` and
+/// `board::take_button` instead.
+///
+/// Example Usage:
+/// ```
+///   let peripherals = Peripherals::take().unwrap();
+///   let pins = peripherals.gpiob.split().pins;
+///   let mut led = pins.p4.unwrap().into_push_pull_output();
+///   led.set_high();
+/// ```
+#[allow(missing_docs)]
+pub struct Peripherals {
+    pub rcc: rcc::RCC,
+    pub flash: flash::Flash,
+    pub crs: crs::CRS,
+    pub nvic: Nvic,
+    pub gpioa: gpio::GpioGroup<gpio::GroupA>,
+    pub gpiob: gpio::GpioGroup<gpio::GroupB>,
+    pub gpioc: gpio::GpioGroup<gpio::GroupC>,
+    pub gpiof: gpio::GpioGroup<gpio::GroupF>,
+}
+
+impl Peripherals {
+    /// Take the peripherals. This returns `Some` the first time it is called, and `None` every
+    /// time after that.
+    pub fn take() -> Option<Peripherals> {
+        let _g = CriticalSection::begin();
+        // UNSAFE: Interrupts are disabled, so nobody else can be checking the flag
+        unsafe {
+            if TAKEN {
+                None
+            }
+            else {
+                TAKEN = true;
+                Some(Peripherals::steal())
+            }
+        }
+    }
+
+    /// Get the peripherals without checking whether they have already been taken.
+    ///
+    /// This is unsafe because it can create a second owner of a peripheral, it should only be
+    /// used where taking them normally isn't possible, like a panic handler.
+    pub unsafe fn steal() -> Peripherals {
+        Peripherals {
+            rcc: rcc::rcc(),
+            flash: flash::flash(),
+            crs: crs::crs(),
+            nvic: interrupt::nvic(),
+            gpioa: gpio::GpioGroup::new(),
+            gpiob: gpio::GpioGroup::new(),
+            gpioc: gpio::GpioGroup::new(),
+            gpiof: gpio::GpioGroup::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_only_succeeds_once() {
+        assert!(Peripherals::take().is_some());
+        assert!(Peripherals::take().is_none());
+    }
+}
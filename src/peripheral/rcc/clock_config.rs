@@ -0,0 +1,309 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module provides a validated configuration of the system clock tree.
+//!
+//! A `ClockConfig` describes the clock driving the system clock, the PLL settings if the PLL is
+//! used, and the AHB and APB prescalers. The configuration is checked against the limits of the
+//! chip before anything is changed, and then applied in an order that keeps the system running
+//! from a valid clock the whole time.
+
+use super::{RCC, Clock, AhbPrescaler, ApbPrescaler};
+use super::clock_control::clock_rate;
+use super::defs::*;
+
+/// Errors that can occur when validating or applying a clock configuration.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClockError {
+    /// The clock can't be used to drive the system clock.
+    InvalidSource(Clock),
+    /// The clock can't be used to drive the PLL.
+    InvalidPllSource(Clock),
+    /// The PLL prediv factor is outside of the range [1..16], or isn't 2 when the PLL is driven
+    /// by the HSI.
+    InvalidPrediv(u8),
+    /// The PLL multiplier is outside of the range [2..16].
+    InvalidMultiplier(u8),
+    /// The clock going into the PLL (in Hz) is outside of the range the PLL accepts.
+    PllInputOutOfRange(u32),
+    /// The clock coming out of the PLL (in Hz) is outside of the range the PLL can produce.
+    PllOutputOutOfRange(u32),
+    /// The resulting system clock (in Hz) is faster than the chip allows.
+    SystemClockTooFast(u32),
+    /// The HSE didn't become ready in time, the system is left running from the HSI.
+    HseStartupTimeout,
+}
+
+/// A configuration for the system clock tree.
+///
+/// Example Usage:
+/// ```
+///   // 8 MHz HSE / 1 * 6 = 48 MHz system clock, 24 MHz APB clock
+///   let config = ClockConfig::pll(Clock::HSE, 1, 6)
+///       .apb_prescaler(ApbPrescaler::Div2);
+///   config.apply(&mut peripherals.rcc).expect("Invalid clock configuration!");
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ClockConfig {
+    source: Clock,
+    pll_source: Clock,
+    prediv: u8,
+    multiplier: u8,
+    ahb_prescaler: AhbPrescaler,
+    apb_prescaler: ApbPrescaler,
+    hse_bypass: bool,
+    clock_security: bool,
+}
+
+impl Default for ClockConfig {
+    /// Run the system clock at 48 MHz from the PLL, driven by the HSI.
+    fn default() -> Self {
+        ClockConfig::pll(Clock::HSI, 2, 12)
+    }
+}
+
+impl ClockConfig {
+    /// Run the system clock directly from the specified clock, with no prescaling.
+    pub fn new(source: Clock) -> Self {
+        ClockConfig {
+            source: source,
+            pll_source: Clock::HSI,
+            prediv: 2,
+            multiplier: 2,
+            ahb_prescaler: AhbPrescaler::Div1,
+            apb_prescaler: ApbPrescaler::Div1,
+            hse_bypass: false,
+            clock_security: false,
+        }
+    }
+
+    /// Run the system clock from the PLL, driven by `source` divided by `prediv` and multiplied by
+    /// `multiplier`.
+    ///
+    /// The HSI is always divided by 2 before reaching the PLL, so when it is the source `prediv`
+    /// must be 2.
+    pub fn pll(source: Clock, prediv: u8, multiplier: u8) -> Self {
+        ClockConfig {
+            source: Clock::PLL,
+            pll_source: source,
+            prediv: prediv,
+            multiplier: multiplier,
+            ahb_prescaler: AhbPrescaler::Div1,
+            apb_prescaler: ApbPrescaler::Div1,
+            hse_bypass: false,
+            clock_security: false,
+        }
+    }
+
+    /// Set the prescaler dividing the system clock to produce the AHB clock.
+    pub fn ahb_prescaler(mut self, prescaler: AhbPrescaler) -> Self {
+        self.ahb_prescaler = prescaler;
+        self
+    }
+
+    /// Set the prescaler dividing the AHB clock to produce the APB clock.
+    pub fn apb_prescaler(mut self, prescaler: ApbPrescaler) -> Self {
+        self.apb_prescaler = prescaler;
+        self
+    }
+
+    /// Drive the HSE from an external clock instead of a crystal if `bypass` is true.
+    pub fn hse_bypass(mut self, bypass: bool) -> Self {
+        self.hse_bypass = bypass;
+        self
+    }
+
+    /// Turn on the clock security system if `enable` is true. This only has an effect when the
+    /// configuration uses the HSE.
+    pub fn clock_security(mut self, enable: bool) -> Self {
+        self.clock_security = enable;
+        self
+    }
+
+    fn uses_hse(&self) -> bool {
+        match self.source {
+            Clock::HSE => true,
+            Clock::PLL => self.pll_source == Clock::HSE,
+            _ => false,
+        }
+    }
+
+    /// Validate the configuration, returning the resulting system clock rate in Hz.
+    pub fn system_clock_rate(&self) -> Result<u32, ClockError> {
+        let rate = match self.source {
+            Clock::HSI => HSI_VALUE,
+            Clock::HSE => clock_rate::get_hse_rate(),
+            Clock::HSI48 => HSI48_VALUE,
+            Clock::PLL => try!(self.pll_rate()),
+            clock => return Err(ClockError::InvalidSource(clock)),
+        };
+
+        if rate > SYSCLK_MAX {
+            return Err(ClockError::SystemClockTooFast(rate));
+        }
+        Ok(rate)
+    }
+
+    fn pll_rate(&self) -> Result<u32, ClockError> {
+        if self.multiplier < 2 || self.multiplier > 16 {
+            return Err(ClockError::InvalidMultiplier(self.multiplier));
+        }
+        if self.prediv < 1 || self.prediv > 16 {
+            return Err(ClockError::InvalidPrediv(self.prediv));
+        }
+
+        let input = match self.pll_source {
+            Clock::HSI if self.prediv == 2 => HSI_VALUE / 2,
+            Clock::HSI => return Err(ClockError::InvalidPrediv(self.prediv)),
+            Clock::HSE => clock_rate::get_hse_rate() / self.prediv as u32,
+            Clock::HSI48 => HSI48_VALUE / self.prediv as u32,
+            clock => return Err(ClockError::InvalidPllSource(clock)),
+        };
+        if input < PLL_INPUT_MIN || input > PLL_INPUT_MAX {
+            return Err(ClockError::PllInputOutOfRange(input));
+        }
+
+        let output = input * self.multiplier as u32;
+        if output < PLL_OUTPUT_MIN || output > PLL_OUTPUT_MAX {
+            return Err(ClockError::PllOutputOutOfRange(output));
+        }
+        Ok(output)
+    }
+
+    /// Validate and apply the configuration, returning the new system clock rate in Hz.
+    ///
+    /// If the configuration is invalid, the clocks are left untouched. While the clocks are being
+    /// reconfigured, the system runs from the HSI. If the HSE doesn't start, the system is left
+    /// running from the HSI and `ClockError::HseStartupTimeout` is returned.
+    pub fn apply(&self, rcc: &mut RCC) -> Result<u32, ClockError> {
+        let rate = try!(self.system_clock_rate());
+
+        // The PLL can't be configured while it's driving the system clock, so switch to the HSI
+        // until everything is ready
+        enable_and_wait(rcc, Clock::HSI);
+        if rcc.get_system_clock_source() != Clock::HSI {
+            rcc.set_system_clock_source(Clock::HSI);
+        }
+        rcc.disable_clock_security();
+
+        if self.source == Clock::PLL {
+            rcc.disable_clock(Clock::PLL);
+            while rcc.clock_is_ready(Clock::PLL) {}
+        }
+
+        if self.uses_hse() {
+            if !rcc.start_hse(self.hse_bypass, HSE_STARTUP_TIMEOUT) {
+                return Err(ClockError::HseStartupTimeout);
+            }
+            if self.clock_security {
+                rcc.enable_clock_security();
+            }
+        }
+
+        match self.source {
+            Clock::PLL => {
+                if self.pll_source != Clock::HSE {
+                    enable_and_wait(rcc, self.pll_source);
+                }
+
+                rcc.set_pll_source(self.pll_source);
+                rcc.set_pll_prediv_factor(self.prediv);
+                rcc.set_pll_multiplier(self.multiplier);
+                enable_and_wait(rcc, Clock::PLL);
+            },
+            Clock::HSE => {},
+            clock => enable_and_wait(rcc, clock),
+        }
+
+        // The buses are divided down before switching so they never run faster than requested
+        rcc.set_apb_prescaler(self.apb_prescaler);
+        rcc.set_ahb_prescaler(self.ahb_prescaler);
+        rcc.set_system_clock_source(self.source);
+
+        Ok(rate)
+    }
+}
+
+fn enable_and_wait(rcc: &mut RCC, clock: Clock) {
+    rcc.enable_clock(clock);
+    while !rcc.clock_is_ready(clock) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_48mhz() {
+        assert_eq!(ClockConfig::default().system_clock_rate(), Ok(48_000_000));
+    }
+
+    #[test]
+    fn test_hsi_config_is_8mhz() {
+        assert_eq!(ClockConfig::new(Clock::HSI).system_clock_rate(), Ok(8_000_000));
+    }
+
+    #[test]
+    fn test_invalid_system_clock_source_is_error() {
+        assert_eq!(ClockConfig::new(Clock::HSI14).system_clock_rate(),
+            Err(ClockError::InvalidSource(Clock::HSI14)));
+    }
+
+    #[test]
+    fn test_pll_faster_than_48mhz_is_error() {
+        assert_eq!(ClockConfig::pll(Clock::HSI, 2, 16).system_clock_rate(),
+            Err(ClockError::PllOutputOutOfRange(64_000_000)));
+    }
+
+    #[test]
+    fn test_pll_multiplier_out_of_range_is_error() {
+        assert_eq!(ClockConfig::pll(Clock::HSI, 2, 17).system_clock_rate(),
+            Err(ClockError::InvalidMultiplier(17)));
+    }
+
+    #[test]
+    fn test_pll_hsi_must_use_prediv_2() {
+        assert_eq!(ClockConfig::pll(Clock::HSI, 1, 6).system_clock_rate(),
+            Err(ClockError::InvalidPrediv(1)));
+    }
+
+    #[test]
+    fn test_pll_input_too_fast_is_error() {
+        assert_eq!(ClockConfig::pll(Clock::HSI48, 1, 2).system_clock_rate(),
+            Err(ClockError::PllInputOutOfRange(48_000_000)));
+    }
+
+    #[test]
+    fn test_pll_output_too_slow_is_error() {
+        assert_eq!(ClockConfig::pll(Clock::HSE, 8, 2).system_clock_rate(),
+            Err(ClockError::PllOutputOutOfRange(2_000_000)));
+    }
+
+    #[test]
+    fn test_uses_hse() {
+        assert!(ClockConfig::new(Clock::HSE).uses_hse());
+        assert!(ClockConfig::pll(Clock::HSE, 1, 6).uses_hse());
+        assert!(!ClockConfig::new(Clock::HSI).uses_hse());
+        assert!(!ClockConfig::default().uses_hse());
+    }
+
+    #[test]
+    fn test_invalid_pll_source_is_error() {
+        assert_eq!(ClockConfig::pll(Clock::PLL, 2, 2).system_clock_rate(),
+            Err(ClockError::InvalidPllSource(Clock::PLL)));
+    }
+}
@@ -20,7 +20,20 @@
 use super::defs::*;
 
 pub mod clock_rate {
+    use super::super::defs::HSE_VALUE;
+
     static mut CLOCK_RATE: u32 = 0;
+    static mut HSE_RATE: u32 = HSE_VALUE;
+
+    pub fn get_hse_rate() -> u32 {
+        unsafe {
+            HSE_RATE
+        }
+    }
+
+    pub fn set_hse_rate(rate: u32) {
+        unsafe { HSE_RATE = rate; }
+    }
 
     pub fn get_system_clock_rate() -> u32 {
         unsafe {
@@ -28,15 +41,16 @@ pub mod clock_rate {
         }
     }
 
-    pub fn update_system_clock_rate() {
+    /// Get the rate the system clock would run at if it were driven by `clock`, with the current
+    /// PLL settings.
+    pub fn source_clock_rate(clock: super::Clock) -> u32 {
         use super::Clock;
-        use super::super::super::systick;
         use super::super::defs::*;
 
-        let rcc = super::super::rcc();
-        let rate = match rcc.get_system_clock_source() {
+        let rcc = super::super::RCC::rcc();
+        match clock {
             Clock::HSI => HSI_VALUE,
-            Clock::HSE => HSE_VALUE,
+            Clock::HSE => get_hse_rate(),
             Clock::HSI48 => HSI48_VALUE,
             Clock::PLL => {
                 let multiplier = rcc.get_pll_multiplier() as u32;
@@ -44,19 +58,28 @@ pub mod clock_rate {
                 let prediv_factor = rcc.get_pll_prediv_factor() as u32;
 
                 match source {
-                    Clock::HSE => (HSE_VALUE/prediv_factor) * multiplier,
+                    Clock::HSE => (get_hse_rate()/prediv_factor) * multiplier,
                     Clock::HSI48 => (HSI48_VALUE/prediv_factor) * multiplier,
                     Clock::HSI => (HSI_VALUE/2) * multiplier,
-                    _ => panic!("CRR::update_system_core_clock - invalid clock driving the PLL!"),
+                    _ => panic!("CRR::source_clock_rate - invalid clock driving the PLL!"),
                 }
             },
-            _ => panic!("CRR::update_system_core_clock - invalid clock for the system clock!"),
-        };
+            _ => panic!("CRR::source_clock_rate - invalid clock for the system clock!"),
+        }
+    }
+
+    pub fn update_system_clock_rate() {
+        use super::super::super::systick;
+
+        let rcc = super::super::RCC::rcc();
+        let rate = source_clock_rate(rcc.get_system_clock_source());
 
         unsafe { CLOCK_RATE = rate; }
+        // The processor (and so the system tick) runs off of the AHB clock
+        let hclk = rate / rcc.get_ahb_prescaler().divisor();
         let mut systick = systick::systick();
         // Interrupt every millisecond
-        systick.set_reload_value(rate / 1000);
+        systick.set_reload_value(hclk / 1000);
     }
 }
 
@@ -141,6 +164,33 @@ impl CR {
 
         (self.0 & mask) != 0
     }
+
+    /// Set the HSE to be bypassed by an external clock if `enable` is true. This can only be
+    /// changed while the HSE is off.
+    pub fn set_hse_bypass(&mut self, enable: bool) {
+        self.0 &= !CR_HSEBYP;
+        if enable {
+            self.0 |= CR_HSEBYP;
+        }
+    }
+
+    /// Return true if the HSE is bypassed by an external clock.
+    pub fn hse_bypass_is_on(&self) -> bool {
+        (self.0 & CR_HSEBYP) != 0
+    }
+
+    /// Set the clock security system on if `enable` is true, off otherwise.
+    pub fn set_clock_security(&mut self, enable: bool) {
+        self.0 &= !CR_CSSON;
+        if enable {
+            self.0 |= CR_CSSON;
+        }
+    }
+
+    /// Return true if the clock security system is on.
+    pub fn clock_security_is_on(&self) -> bool {
+        (self.0 & CR_CSSON) != 0
+    }
 }
 
 /// The CR2 register only controls the HSI48 and HSI14 clocks. If another clock is passed in as an
@@ -314,6 +364,30 @@ mod tests {
         cr.clock_is_ready(Clock::HSI48);
     }
 
+    #[test]
+    fn test_cr_set_hse_bypass() {
+        let mut cr = CR(0b1 << 16);
+
+        cr.set_hse_bypass(true);
+        assert_eq!(cr.0, 0b1 << 16 | 0b1 << 18);
+        assert!(cr.hse_bypass_is_on());
+
+        cr.set_hse_bypass(false);
+        assert_eq!(cr.0, 0b1 << 16);
+    }
+
+    #[test]
+    fn test_cr_set_clock_security() {
+        let mut cr = CR(0);
+
+        cr.set_clock_security(true);
+        assert_eq!(cr.0, 0b1 << 19);
+        assert!(cr.clock_security_is_on());
+
+        cr.set_clock_security(false);
+        assert_eq!(cr.0, 0);
+    }
+
     #[test]
     fn test_cr2_set_hsi48_clock_on() {
         let mut cr2 = CR2(0);
@@ -0,0 +1,56 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module handles the CIR register, which deals with the clock interrupt flags.
+
+use super::defs::*;
+
+/// Clock Interrupt Register
+#[derive(Copy, Clone, Debug)]
+pub struct CIR(u32);
+
+impl CIR {
+    /// Return true if the clock security system detected a failure of the HSE.
+    pub fn css_failure_detected(&self) -> bool {
+        self.0 & CIR_CSSF != 0
+    }
+
+    /// Clear the clock security system interrupt flag.
+    pub fn clear_css_flag(&mut self) {
+        // The clear bits always read as 0, so this leaves the interrupt enables alone
+        self.0 |= CIR_CSSC;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cir_css_failure_detected() {
+        assert_eq!(CIR(0).css_failure_detected(), false);
+        assert_eq!(CIR(0b1 << 7).css_failure_detected(), true);
+    }
+
+    #[test]
+    fn test_cir_clear_css_flag() {
+        let mut cir = CIR(0);
+
+        cir.clear_css_flag();
+        assert_eq!(cir.0, 0b1 << 23);
+    }
+}
@@ -15,11 +15,213 @@
 * along with this program. If not, see <http://www.gnu.org/licenses/>.
 */
 
-//! This module handles the CFGR register, which deals with clock configuration.
+//! This module handles the CFGR registers, which deal with clock configuration.
 
-use super::Clock;
+use super::{Clock, Peripheral};
 use super::defs::*;
 
+/// Division factor applied to the system clock to produce the AHB clock (HCLK).
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AhbPrescaler {
+    Div1,
+    Div2,
+    Div4,
+    Div8,
+    Div16,
+    Div64,
+    Div128,
+    Div256,
+    Div512,
+}
+
+impl AhbPrescaler {
+    /// Get the number the system clock is divided by.
+    pub fn divisor(&self) -> u32 {
+        match *self {
+            AhbPrescaler::Div1 => 1,
+            AhbPrescaler::Div2 => 2,
+            AhbPrescaler::Div4 => 4,
+            AhbPrescaler::Div8 => 8,
+            AhbPrescaler::Div16 => 16,
+            AhbPrescaler::Div64 => 64,
+            AhbPrescaler::Div128 => 128,
+            AhbPrescaler::Div256 => 256,
+            AhbPrescaler::Div512 => 512,
+        }
+    }
+
+    fn mask(&self) -> u32 {
+        match *self {
+            AhbPrescaler::Div1 => CFGR_HPRE_DIV1,
+            AhbPrescaler::Div2 => CFGR_HPRE_DIV2,
+            AhbPrescaler::Div4 => CFGR_HPRE_DIV4,
+            AhbPrescaler::Div8 => CFGR_HPRE_DIV8,
+            AhbPrescaler::Div16 => CFGR_HPRE_DIV16,
+            AhbPrescaler::Div64 => CFGR_HPRE_DIV64,
+            AhbPrescaler::Div128 => CFGR_HPRE_DIV128,
+            AhbPrescaler::Div256 => CFGR_HPRE_DIV256,
+            AhbPrescaler::Div512 => CFGR_HPRE_DIV512,
+        }
+    }
+
+    fn from_mask(mask: u32) -> Self {
+        if mask & CFGR_HPRE_DIV_BIT == 0 {
+            return AhbPrescaler::Div1;
+        }
+        match mask {
+            CFGR_HPRE_DIV2 => AhbPrescaler::Div2,
+            CFGR_HPRE_DIV4 => AhbPrescaler::Div4,
+            CFGR_HPRE_DIV8 => AhbPrescaler::Div8,
+            CFGR_HPRE_DIV16 => AhbPrescaler::Div16,
+            CFGR_HPRE_DIV64 => AhbPrescaler::Div64,
+            CFGR_HPRE_DIV128 => AhbPrescaler::Div128,
+            CFGR_HPRE_DIV256 => AhbPrescaler::Div256,
+            CFGR_HPRE_DIV512 => AhbPrescaler::Div512,
+            _ => panic!("AhbPrescaler::from_mask - mask was not a valid value!"),
+        }
+    }
+}
+
+/// Division factor applied to the AHB clock to produce the APB clock (PCLK).
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ApbPrescaler {
+    Div1,
+    Div2,
+    Div4,
+    Div8,
+    Div16,
+}
+
+impl ApbPrescaler {
+    /// Get the number the AHB clock is divided by.
+    pub fn divisor(&self) -> u32 {
+        match *self {
+            ApbPrescaler::Div1 => 1,
+            ApbPrescaler::Div2 => 2,
+            ApbPrescaler::Div4 => 4,
+            ApbPrescaler::Div8 => 8,
+            ApbPrescaler::Div16 => 16,
+        }
+    }
+
+    fn mask(&self) -> u32 {
+        match *self {
+            ApbPrescaler::Div1 => CFGR_PPRE_DIV1,
+            ApbPrescaler::Div2 => CFGR_PPRE_DIV2,
+            ApbPrescaler::Div4 => CFGR_PPRE_DIV4,
+            ApbPrescaler::Div8 => CFGR_PPRE_DIV8,
+            ApbPrescaler::Div16 => CFGR_PPRE_DIV16,
+        }
+    }
+
+    fn from_mask(mask: u32) -> Self {
+        if mask & CFGR_PPRE_DIV_BIT == 0 {
+            return ApbPrescaler::Div1;
+        }
+        match mask {
+            CFGR_PPRE_DIV2 => ApbPrescaler::Div2,
+            CFGR_PPRE_DIV4 => ApbPrescaler::Div4,
+            CFGR_PPRE_DIV8 => ApbPrescaler::Div8,
+            CFGR_PPRE_DIV16 => ApbPrescaler::Div16,
+            _ => panic!("ApbPrescaler::from_mask - mask was not a valid value!"),
+        }
+    }
+}
+
+/// The clock output on the MCO pin.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum McoSource {
+    /// Nothing is output
+    Disabled,
+    /// The 14 MHz High Speed Internal clock
+    HSI14,
+    /// The Low Speed Internal clock
+    LSI,
+    /// The Low Speed External clock
+    LSE,
+    /// The system clock
+    SYSCLK,
+    /// The High Speed Internal clock
+    HSI,
+    /// The High Speed External clock
+    HSE,
+    /// The PLL
+    PLL,
+    /// The PLL divided by 2
+    PLLDiv2,
+    /// The 48 MHz High Speed Internal clock
+    HSI48,
+}
+
+impl McoSource {
+    fn mask(&self) -> u32 {
+        match *self {
+            McoSource::Disabled => CFGR_MCO_DISABLED,
+            McoSource::HSI14 => CFGR_MCO_HSI14,
+            McoSource::LSI => CFGR_MCO_LSI,
+            McoSource::LSE => CFGR_MCO_LSE,
+            McoSource::SYSCLK => CFGR_MCO_SYSCLK,
+            McoSource::HSI => CFGR_MCO_HSI,
+            McoSource::HSE => CFGR_MCO_HSE,
+            McoSource::PLL => CFGR_MCO_PLL | CFGR_PLLNODIV,
+            McoSource::PLLDiv2 => CFGR_MCO_PLL,
+            McoSource::HSI48 => CFGR_MCO_HSI48,
+        }
+    }
+
+    fn from_mask(mask: u32) -> Self {
+        match mask & CFGR_MCO_MASK {
+            CFGR_MCO_DISABLED => McoSource::Disabled,
+            CFGR_MCO_HSI14 => McoSource::HSI14,
+            CFGR_MCO_LSI => McoSource::LSI,
+            CFGR_MCO_LSE => McoSource::LSE,
+            CFGR_MCO_SYSCLK => McoSource::SYSCLK,
+            CFGR_MCO_HSI => McoSource::HSI,
+            CFGR_MCO_HSE => McoSource::HSE,
+            CFGR_MCO_PLL if mask & CFGR_PLLNODIV != 0 => McoSource::PLL,
+            CFGR_MCO_PLL => McoSource::PLLDiv2,
+            CFGR_MCO_HSI48 => McoSource::HSI48,
+            _ => panic!("McoSource::from_mask - mask was not a valid value!"),
+        }
+    }
+}
+
+/// Division factor applied to the clock output on the MCO pin.
+#[allow(missing_docs)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum McoPrescaler {
+    Div1,
+    Div2,
+    Div4,
+    Div8,
+    Div16,
+    Div32,
+    Div64,
+    Div128,
+}
+
+impl McoPrescaler {
+    fn mask(&self) -> u32 {
+        (*self as u32) << 28
+    }
+
+    fn from_mask(mask: u32) -> Self {
+        match mask >> 28 {
+            0 => McoPrescaler::Div1,
+            1 => McoPrescaler::Div2,
+            2 => McoPrescaler::Div4,
+            3 => McoPrescaler::Div8,
+            4 => McoPrescaler::Div16,
+            5 => McoPrescaler::Div32,
+            6 => McoPrescaler::Div64,
+            7 => McoPrescaler::Div128,
+            _ => panic!("McoPrescaler::from_mask - mask was not a valid value!"),
+        }
+    }
+}
+
 /// Clock Configuration Register
 #[derive(Copy, Clone, Debug)]
 pub struct CFGR(u32);
@@ -96,6 +298,42 @@ impl CFGR {
         self.0 &= !CFGR_PLLMUL_MASK;
         self.0 |= mask;
     }
+
+    pub fn get_ahb_prescaler(&self) -> AhbPrescaler {
+        AhbPrescaler::from_mask(self.0 & CFGR_HPRE_MASK)
+    }
+
+    pub fn set_ahb_prescaler(&mut self, prescaler: AhbPrescaler) {
+        self.0 &= !CFGR_HPRE_MASK;
+        self.0 |= prescaler.mask();
+    }
+
+    pub fn get_apb_prescaler(&self) -> ApbPrescaler {
+        ApbPrescaler::from_mask(self.0 & CFGR_PPRE_MASK)
+    }
+
+    pub fn set_apb_prescaler(&mut self, prescaler: ApbPrescaler) {
+        self.0 &= !CFGR_PPRE_MASK;
+        self.0 |= prescaler.mask();
+    }
+
+    pub fn get_mco_source(&self) -> McoSource {
+        McoSource::from_mask(self.0 & (CFGR_MCO_MASK | CFGR_PLLNODIV))
+    }
+
+    pub fn set_mco_source(&mut self, source: McoSource) {
+        self.0 &= !(CFGR_MCO_MASK | CFGR_PLLNODIV);
+        self.0 |= source.mask();
+    }
+
+    pub fn get_mco_prescaler(&self) -> McoPrescaler {
+        McoPrescaler::from_mask(self.0 & CFGR_MCOPRE_MASK)
+    }
+
+    pub fn set_mco_prescaler(&mut self, prescaler: McoPrescaler) {
+        self.0 &= !CFGR_MCOPRE_MASK;
+        self.0 |= prescaler.mask();
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -121,6 +359,74 @@ impl CFGR2 {
     }
 }
 
+/// The clock driving a USART that has a selectable clock source.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UsartClockSource {
+    /// The APB clock
+    Pclk,
+    /// The system clock
+    Sysclk,
+    /// The Low Speed External clock
+    Lse,
+    /// The High Speed Internal clock
+    Hsi,
+}
+
+impl UsartClockSource {
+    fn mask(&self) -> u32 {
+        match *self {
+            UsartClockSource::Pclk => CFGR3_USARTSW_PCLK,
+            UsartClockSource::Sysclk => CFGR3_USARTSW_SYSCLK,
+            UsartClockSource::Lse => CFGR3_USARTSW_LSE,
+            UsartClockSource::Hsi => CFGR3_USARTSW_HSI,
+        }
+    }
+
+    fn from_mask(mask: u32) -> Self {
+        match mask {
+            CFGR3_USARTSW_PCLK => UsartClockSource::Pclk,
+            CFGR3_USARTSW_SYSCLK => UsartClockSource::Sysclk,
+            CFGR3_USARTSW_LSE => UsartClockSource::Lse,
+            CFGR3_USARTSW_HSI => UsartClockSource::Hsi,
+            _ => panic!("UsartClockSource::from_mask - mask was not a valid value!"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct CFGR3(u32);
+
+impl CFGR3 {
+    /// Return true if the clock source of the specified peripheral is selected in this register.
+    pub fn serves_peripheral(&self, peripheral: Peripheral) -> bool {
+        match peripheral {
+            Peripheral::USART1 | Peripheral::USART2 | Peripheral::USART3 => true,
+            _ => false,
+        }
+    }
+
+    pub fn get_usart_clock_source(&self, usart: Peripheral) -> UsartClockSource {
+        let shift = Self::usart_shift(usart);
+        UsartClockSource::from_mask((self.0 >> shift) & CFGR3_USARTSW_MASK)
+    }
+
+    pub fn set_usart_clock_source(&mut self, usart: Peripheral, source: UsartClockSource) {
+        let shift = Self::usart_shift(usart);
+
+        self.0 &= !(CFGR3_USARTSW_MASK << shift);
+        self.0 |= source.mask() << shift;
+    }
+
+    fn usart_shift(usart: Peripheral) -> u32 {
+        match usart {
+            Peripheral::USART1 => CFGR3_USART1SW_SHIFT,
+            Peripheral::USART2 => CFGR3_USART2SW_SHIFT,
+            Peripheral::USART3 => CFGR3_USART3SW_SHIFT,
+            _ => panic!("CFGR3::usart_shift - only USART1, USART2 and USART3 have a selectable clock source!"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,6 +518,72 @@ mod tests {
         cfgr.set_pll_multiplier(17);
     }
 
+    #[test]
+    fn test_cfgr_set_ahb_prescaler() {
+        let mut cfgr = CFGR(0);
+
+        cfgr.set_ahb_prescaler(AhbPrescaler::Div64);
+        assert_eq!(cfgr.0, 0b1100 << 4);
+
+        cfgr.set_ahb_prescaler(AhbPrescaler::Div1);
+        assert_eq!(cfgr.0, 0);
+    }
+
+    #[test]
+    fn test_cfgr_get_ahb_prescaler() {
+        let cfgr = CFGR(0b1001 << 4);
+
+        assert_eq!(cfgr.get_ahb_prescaler(), AhbPrescaler::Div4);
+    }
+
+    #[test]
+    fn test_cfgr_get_ahb_prescaler_undivided_ignores_low_bits() {
+        let cfgr = CFGR(0b0101 << 4);
+
+        assert_eq!(cfgr.get_ahb_prescaler(), AhbPrescaler::Div1);
+    }
+
+    #[test]
+    fn test_cfgr_set_apb_prescaler() {
+        let mut cfgr = CFGR(0);
+
+        cfgr.set_apb_prescaler(ApbPrescaler::Div8);
+        assert_eq!(cfgr.0, 0b110 << 8);
+    }
+
+    #[test]
+    fn test_cfgr_get_apb_prescaler() {
+        let cfgr = CFGR(0b111 << 8);
+
+        assert_eq!(cfgr.get_apb_prescaler(), ApbPrescaler::Div16);
+    }
+
+    #[test]
+    fn test_cfgr_set_mco_source() {
+        let mut cfgr = CFGR(0);
+
+        cfgr.set_mco_source(McoSource::SYSCLK);
+        assert_eq!(cfgr.0, 0b0100 << 24);
+        assert_eq!(cfgr.get_mco_source(), McoSource::SYSCLK);
+
+        cfgr.set_mco_source(McoSource::PLL);
+        assert_eq!(cfgr.0, 0b1 << 31 | 0b0111 << 24);
+        assert_eq!(cfgr.get_mco_source(), McoSource::PLL);
+
+        cfgr.set_mco_source(McoSource::PLLDiv2);
+        assert_eq!(cfgr.0, 0b0111 << 24);
+        assert_eq!(cfgr.get_mco_source(), McoSource::PLLDiv2);
+    }
+
+    #[test]
+    fn test_cfgr_set_mco_prescaler() {
+        let mut cfgr = CFGR(0b0100 << 24);
+
+        cfgr.set_mco_prescaler(McoPrescaler::Div128);
+        assert_eq!(cfgr.0, 0b111 << 28 | 0b0100 << 24);
+        assert_eq!(cfgr.get_mco_prescaler(), McoPrescaler::Div128);
+    }
+
     #[test]
     fn test_cfgr2_get_pll_prediv_factor() {
         // Prediv factor field starts at 12, should get 13 out
@@ -243,4 +615,40 @@ mod tests {
 
         cfgr2.set_pll_prediv_factor(17);
     }
+
+    #[test]
+    fn test_cfgr3_get_usart_clock_source() {
+        let cfgr3 = CFGR3(0b11 << 16 | 0b01);
+
+        assert_eq!(cfgr3.get_usart_clock_source(Peripheral::USART1), UsartClockSource::Sysclk);
+        assert_eq!(cfgr3.get_usart_clock_source(Peripheral::USART2), UsartClockSource::Hsi);
+        assert_eq!(cfgr3.get_usart_clock_source(Peripheral::USART3), UsartClockSource::Pclk);
+    }
+
+    #[test]
+    fn test_cfgr3_set_usart_clock_source() {
+        let mut cfgr3 = CFGR3(0b11 << 16);
+
+        cfgr3.set_usart_clock_source(Peripheral::USART2, UsartClockSource::Lse);
+        assert_eq!(cfgr3.0, 0b10 << 16);
+
+        cfgr3.set_usart_clock_source(Peripheral::USART3, UsartClockSource::Hsi);
+        assert_eq!(cfgr3.0, 0b11 << 18 | 0b10 << 16);
+    }
+
+    #[test]
+    fn test_cfgr3_serves_peripheral() {
+        let cfgr3 = CFGR3(0);
+
+        assert!(cfgr3.serves_peripheral(Peripheral::USART1));
+        assert!(!cfgr3.serves_peripheral(Peripheral::USART4));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cfgr3_set_usart_clock_source_fixed_usart_panics() {
+        let mut cfgr3 = CFGR3(0);
+
+        cfgr3.set_usart_clock_source(Peripheral::USART4, UsartClockSource::Hsi);
+    }
 }
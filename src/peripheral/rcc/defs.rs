@@ -18,8 +18,19 @@
 pub const RCC_ADDR: *const u32 = 0x4002_1000 as *const _;
 
 pub const HSI_VALUE: u32 = 8_000_000;
+// Default HSE frequency, boards with a different crystal should set it with `set_hse_frequency`
 pub const HSE_VALUE: u32 = 8_000_000;
+// Number of times to poll for the HSE to become ready before giving up
+pub const HSE_STARTUP_TIMEOUT: u32 = 0x5000;
 pub const HSI48_VALUE: u32 = 48_000_000;
+pub const LSE_VALUE: u32 = 32_768;
+
+// Chip limits for the clock tree
+pub const SYSCLK_MAX: u32 = 48_000_000;
+pub const PLL_INPUT_MIN: u32 = 1_000_000;
+pub const PLL_INPUT_MAX: u32 = 24_000_000;
+pub const PLL_OUTPUT_MIN: u32 = 16_000_000;
+pub const PLL_OUTPUT_MAX: u32 = 48_000_000;
 
 pub const CR_OFFSET: u32 = 0x00;
 
@@ -27,6 +38,8 @@ pub const CR_HSION: u32 = 0b1 << 0;
 pub const CR_HSIRDY: u32 = 0b1 << 1;
 pub const CR_HSEON: u32 = 0b1 << 16;
 pub const CR_HSERDY: u32 = 0b1 << 17;
+pub const CR_HSEBYP: u32 = 0b1 << 18;
+pub const CR_CSSON: u32 = 0b1 << 19;
 pub const CR_PLLON: u32 = 0b1 << 24;
 pub const CR_PLLRDY: u32 = 0b1 << 25;
 
@@ -52,6 +65,47 @@ pub const CFGR_PLLSRC_HSI48_PREDIV: u32 = CFGR_CLOCK_HSI48 << 15;
 
 pub const CFGR_PLLMUL_MASK: u32 = 0b1111 << 18;
 
+pub const CFGR_HPRE_MASK: u32 = 0b1111 << 4;
+pub const CFGR_HPRE_DIV1: u32 = 0b0000 << 4;
+pub const CFGR_HPRE_DIV2: u32 = 0b1000 << 4;
+pub const CFGR_HPRE_DIV4: u32 = 0b1001 << 4;
+pub const CFGR_HPRE_DIV8: u32 = 0b1010 << 4;
+pub const CFGR_HPRE_DIV16: u32 = 0b1011 << 4;
+pub const CFGR_HPRE_DIV64: u32 = 0b1100 << 4;
+pub const CFGR_HPRE_DIV128: u32 = 0b1101 << 4;
+pub const CFGR_HPRE_DIV256: u32 = 0b1110 << 4;
+pub const CFGR_HPRE_DIV512: u32 = 0b1111 << 4;
+// If the top bit of the field is clear, the clock is not divided
+pub const CFGR_HPRE_DIV_BIT: u32 = 0b1000 << 4;
+
+pub const CFGR_PPRE_MASK: u32 = 0b111 << 8;
+pub const CFGR_PPRE_DIV1: u32 = 0b000 << 8;
+pub const CFGR_PPRE_DIV2: u32 = 0b100 << 8;
+pub const CFGR_PPRE_DIV4: u32 = 0b101 << 8;
+pub const CFGR_PPRE_DIV8: u32 = 0b110 << 8;
+pub const CFGR_PPRE_DIV16: u32 = 0b111 << 8;
+// If the top bit of the field is clear, the clock is not divided
+pub const CFGR_PPRE_DIV_BIT: u32 = 0b100 << 8;
+
+pub const CFGR_MCO_MASK: u32 = 0b1111 << 24;
+pub const CFGR_MCO_DISABLED: u32 = 0b0000 << 24;
+pub const CFGR_MCO_HSI14: u32 = 0b0001 << 24;
+pub const CFGR_MCO_LSI: u32 = 0b0010 << 24;
+pub const CFGR_MCO_LSE: u32 = 0b0011 << 24;
+pub const CFGR_MCO_SYSCLK: u32 = 0b0100 << 24;
+pub const CFGR_MCO_HSI: u32 = 0b0101 << 24;
+pub const CFGR_MCO_HSE: u32 = 0b0110 << 24;
+pub const CFGR_MCO_PLL: u32 = 0b0111 << 24;
+pub const CFGR_MCO_HSI48: u32 = 0b1000 << 24;
+
+pub const CFGR_MCOPRE_MASK: u32 = 0b111 << 28;
+pub const CFGR_PLLNODIV: u32 = 0b1 << 31;
+
+// CIR Bit Offsets
+pub const CIR_OFFSET: u32 = 0x08;
+pub const CIR_CSSF: u32 = 0b1 << 7;
+pub const CIR_CSSC: u32 = 0b1 << 23;
+
 // AHBENR Bit Offsets
 pub const AHBENR_OFFSET: u32 = 0x14;
 pub const TSCEN: u32 = 0b1 << 24;
@@ -102,10 +156,26 @@ pub const SPI1EN: u32 = 0b1 << 12;
 pub const ADCEN: u32 = 0b1 << 9;
 pub const SYSCFGCOMPEN: u32 = 0b1 << 0;
 
+// Reset Register Offsets, the reset bits are at the same positions as the enable bits
+pub const APBRSTR2_OFFSET: u32 = 0x0C;
+pub const APBRSTR1_OFFSET: u32 = 0x10;
+pub const AHBRSTR_OFFSET: u32 = 0x28;
+
 // CFGR2 Bit Offsets
 pub const CFGR2_OFFSET: u32 = 0x2C;
 pub const CFGR2_PREDIV_MASK: u32 = 0b1111;
 
+// CFGR3 Bit Offsets
+pub const CFGR3_OFFSET: u32 = 0x30;
+pub const CFGR3_USART1SW_SHIFT: u32 = 0;
+pub const CFGR3_USART2SW_SHIFT: u32 = 16;
+pub const CFGR3_USART3SW_SHIFT: u32 = 18;
+pub const CFGR3_USARTSW_MASK: u32 = 0b11;
+pub const CFGR3_USARTSW_PCLK: u32 = 0b00;
+pub const CFGR3_USARTSW_SYSCLK: u32 = 0b01;
+pub const CFGR3_USARTSW_LSE: u32 = 0b10;
+pub const CFGR3_USARTSW_HSI: u32 = 0b11;
+
 // CR2 Bit Offsets
 pub const CR2_OFFSET: u32 = 0x34;
 pub const CR2_HSI14ON: u32 = 0b1 << 0;
@@ -19,49 +19,117 @@
 //! clocks, setting clock configurations and the reset flags that are set on a reset.
 
 mod clock_control;
+mod clock_config;
+mod clock_interrupt;
 mod config;
 mod enable;
+mod reset;
 mod defs;
 
 use core::ops::{Deref, DerefMut};
+use core::ptr;
 use volatile::Volatile;
 use arm::asm::dsb;
+use peripheral::{flash, gpio};
+use peripheral::gpio::af::Mco;
 use self::defs::*;
 
 use self::clock_control::{CR, CR2};
-use self::config::{CFGR, CFGR2};
+use self::clock_interrupt::CIR;
+use self::config::{CFGR, CFGR2, CFGR3};
 use self::enable::{AHBENR, APBENR1, APBENR2};
+use self::reset::{AHBRSTR, APBRSTR1, APBRSTR2};
 
 pub use self::clock_control::Clock;
+pub use self::clock_config::{ClockConfig, ClockError};
+pub use self::config::{AhbPrescaler, ApbPrescaler, UsartClockSource, McoSource, McoPrescaler};
 pub use self::enable::Peripheral;
 
 /// Returns an instance of the RCC struct so it can be used to modify clock configuration.
-pub fn rcc() -> RCC {
+///
+/// This is unsafe because it creates a second owner of the RCC, application code should get it
+/// from `Peripherals::take` instead.
+#[doc(hidden)]
+pub unsafe fn rcc() -> RCC {
     RCC::rcc()
 }
 
+/// Set the frequency (in Hz) of the crystal or external clock connected to the HSE pins.
+///
+/// This defaults to 8 MHz, and must be set before the HSE is used to drive the system clock or
+/// the PLL so that clock rates are calculated correctly.
+pub fn set_hse_frequency(rate: u32) {
+    clock_control::clock_rate::set_hse_rate(rate);
+}
+
+/// Get the frequency (in Hz) of the crystal or external clock connected to the HSE pins.
+pub fn get_hse_frequency() -> u32 {
+    clock_control::clock_rate::get_hse_rate()
+}
+
+/// Output a clock on the MCO pin (PA8), divided by `prescaler`.
+///
+/// The pin is set to high speed push-pull with no pull resistor. Its type makes sure it is PA8
+/// in alternate function 0, which can only come from splitting GPIOA, so nothing else can be
+/// using it. The clock being output must already be running.
+///
+/// Example Usage:
+/// ```
+///   let pins = peripherals.gpioa.split().pins;
+///   let mco = pins.p8.unwrap().into_alternate::<AF0>();
+///   rcc::enable_mco(&mut peripherals.rcc, mco, McoSource::SYSCLK, McoPrescaler::Div1);
+/// ```
+pub fn enable_mco<MCO: Mco>(rcc: &mut RCC, mut pin: MCO, source: McoSource,
+    prescaler: McoPrescaler) {
+
+    pin.configure(gpio::Speed::High, gpio::Type::PushPull, gpio::Pull::Neither);
+
+    rcc.set_mco_prescaler(prescaler);
+    rcc.set_mco_source(source);
+}
+
+/// Stop outputting a clock on the MCO pin.
+pub fn disable_mco(rcc: &mut RCC) {
+    rcc.set_mco_source(McoSource::Disabled);
+}
+
+/// Get the rate (in Hz) of the AHB clock, which drives the processor and AHB peripherals.
+pub fn hclk_rate(rcc: &RCC) -> u32 {
+    rcc.get_hclk_rate()
+}
+
+/// Get the rate (in Hz) of the APB clock, which drives the APB peripherals.
+pub fn pclk_rate(rcc: &RCC) -> u32 {
+    rcc.get_pclk_rate()
+}
+
+/// Get the rate (in Hz) of the clock driving the specified peripheral.
+pub fn peripheral_clock_rate(rcc: &RCC, peripheral: Peripheral) -> u32 {
+    rcc.get_peripheral_clock_rate(peripheral)
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 #[doc(hidden)]
 pub struct RawRCC {
     cr: CR,
     cfgr: CFGR,
-    cir: u32,
-    apb2rstr: u32,
-    apb1rstr: u32,
+    cir: CIR,
+    apbrstr2: APBRSTR2,
+    apbrstr1: APBRSTR1,
     ahbenr: AHBENR,
     apbenr2: APBENR2,
     apbenr1: APBENR1,
     bdcr: u32,
     csr: u32,
-    ahbrstr: u32,
+    ahbrstr: AHBRSTR,
     cfgr2: CFGR2,
-    cfgr3: u32,
+    cfgr3: CFGR3,
     cr2: CR2,
 }
 
 /// Reset and Clock Controller
-#[derive(Copy, Clone, Debug)]
+#[derive(Debug)]
 pub struct RCC(Volatile<RawRCC>);
 
 impl RCC {
@@ -120,6 +188,68 @@ impl RawRCC {
         }
     }
 
+    /// Start the HSE, polling up to `timeout` times for it to become ready. If `bypass` is true the
+    /// HSE oscillator is bypassed and the HSE is driven by an external clock instead of a crystal.
+    ///
+    /// If the HSE doesn't become ready in time it is turned back off and this method returns
+    /// false. The HSE must not be driving the system clock or the PLL when this is called.
+    pub fn start_hse(&mut self, bypass: bool, timeout: u32) -> bool {
+        // The bypass can only be changed while the HSE is off
+        if self.cr.clock_is_on(Clock::HSE) && self.cr.hse_bypass_is_on() != bypass {
+            self.cr.set_clock(false, Clock::HSE);
+            while self.cr.clock_is_ready(Clock::HSE) {}
+        }
+        self.cr.set_hse_bypass(bypass);
+        self.cr.set_clock(true, Clock::HSE);
+
+        for _ in 0..timeout {
+            if self.cr.clock_is_ready(Clock::HSE) {
+                return true;
+            }
+        }
+
+        self.cr.set_clock(false, Clock::HSE);
+        self.cr.set_hse_bypass(false);
+        false
+    }
+
+    /// Return true if the HSE is bypassed by an external clock.
+    pub fn hse_bypass_is_on(&self) -> bool {
+        self.cr.hse_bypass_is_on()
+    }
+
+    /// Turn on the clock security system. If the HSE fails while it is on, the hardware turns it
+    /// off, switches the system clock to the HSI and raises an NMI.
+    pub fn enable_clock_security(&mut self) {
+        self.cr.set_clock_security(true);
+    }
+
+    /// Turn off the clock security system.
+    pub fn disable_clock_security(&mut self) {
+        self.cr.set_clock_security(false);
+    }
+
+    /// Return true if the clock security system is on.
+    pub fn clock_security_is_on(&self) -> bool {
+        self.cr.clock_security_is_on()
+    }
+
+    /// Return true if the clock security system has detected a failure of the HSE.
+    pub fn clock_security_failure_detected(&self) -> bool {
+        self.cir.css_failure_detected()
+    }
+
+    /// Handle a failure of the HSE detected by the clock security system.
+    ///
+    /// The hardware has already moved the system clock to the HSI, this clears the failure flag
+    /// and brings the recorded clock rate and the flash wait states back in line with the HSI.
+    pub fn recover_from_hse_failure(&mut self) {
+        self.cir.clear_css_flag();
+        self.cr.set_clock_security(false);
+        self.cr.set_hse_bypass(false);
+        self.set_system_clock_source(Clock::HSI);
+    }
+
     /// Return the clock driving the system clock.
     pub fn get_system_clock_source(&self) -> Clock {
         self.cfgr.get_system_clock_source()
@@ -127,13 +257,30 @@ impl RawRCC {
 
     /// Set the system clock source. The system clock can only be run off of the HSI, HSE, PLL, or
     /// HSI48 clocks. If another clock is specified, the kernel will panic.
+    ///
+    /// The flash wait states are adjusted to match the new system clock rate, before the switch if
+    /// the clock is speeding up, and after if it is slowing down.
     pub fn set_system_clock_source(&mut self, clock: Clock) {
+        let current_rate = self.get_system_clock_rate();
+        let new_rate = clock_control::clock_rate::source_clock_rate(clock);
+        // UNSAFE: The wait states have to follow the system clock, whoever owns the flash
+        let mut flash = unsafe { flash::flash() };
+        if new_rate > current_rate {
+            flash.configure_for_system_clock(new_rate);
+        }
+
         self.cfgr.set_system_clock_source(clock);
         // We need a memory barrier here since the hardware is writing to the system clock bit
         // the barrier ensures that the write to the control register takes effect before we
         // try to access the clock rate
         unsafe { dsb(); }
+        // The switch isn't immediate, wait until the hardware reports the new clock is in use
+        while self.cfgr.get_system_clock_source() != clock {}
         clock_control::clock_rate::update_system_clock_rate();
+
+        if new_rate <= current_rate {
+            flash.configure_for_system_clock(new_rate);
+        }
     }
 
     /// Get the clock driving the PLL.
@@ -169,11 +316,111 @@ impl RawRCC {
         self.cfgr2.set_pll_prediv_factor(factor);
     }
 
+    /// Get the prescaler dividing the system clock to produce the AHB clock.
+    pub fn get_ahb_prescaler(&self) -> AhbPrescaler {
+        self.cfgr.get_ahb_prescaler()
+    }
+
+    /// Set the prescaler dividing the system clock to produce the AHB clock.
+    ///
+    /// The processor runs off of the AHB clock, so the system tick is updated to match.
+    pub fn set_ahb_prescaler(&mut self, prescaler: AhbPrescaler) {
+        self.cfgr.set_ahb_prescaler(prescaler);
+        unsafe { dsb(); }
+        clock_control::clock_rate::update_system_clock_rate();
+    }
+
+    /// Get the prescaler dividing the AHB clock to produce the APB clock.
+    pub fn get_apb_prescaler(&self) -> ApbPrescaler {
+        self.cfgr.get_apb_prescaler()
+    }
+
+    /// Set the prescaler dividing the AHB clock to produce the APB clock.
+    pub fn set_apb_prescaler(&mut self, prescaler: ApbPrescaler) {
+        self.cfgr.set_apb_prescaler(prescaler);
+    }
+
+    /// Get the clock output on the MCO pin.
+    pub fn get_mco_source(&self) -> McoSource {
+        self.cfgr.get_mco_source()
+    }
+
+    /// Set the clock output on the MCO pin. This doesn't configure the pin, see `rcc::enable_mco`.
+    pub fn set_mco_source(&mut self, source: McoSource) {
+        self.cfgr.set_mco_source(source);
+    }
+
+    /// Get the factor the clock output on the MCO pin is divided by.
+    pub fn get_mco_prescaler(&self) -> McoPrescaler {
+        self.cfgr.get_mco_prescaler()
+    }
+
+    /// Set the factor the clock output on the MCO pin is divided by.
+    pub fn set_mco_prescaler(&mut self, prescaler: McoPrescaler) {
+        self.cfgr.set_mco_prescaler(prescaler);
+    }
+
     /// Get the rate of the current system clock.
     pub fn get_system_clock_rate(&self) -> u32 {
         clock_control::clock_rate::get_system_clock_rate()
     }
 
+    /// Get the rate of the AHB clock.
+    pub fn get_hclk_rate(&self) -> u32 {
+        self.get_system_clock_rate() / self.cfgr.get_ahb_prescaler().divisor()
+    }
+
+    /// Get the rate of the APB clock.
+    pub fn get_pclk_rate(&self) -> u32 {
+        self.get_hclk_rate() / self.cfgr.get_apb_prescaler().divisor()
+    }
+
+    /// Get the clock source of a USART. Only USART1, USART2 and USART3 have a selectable clock
+    /// source, if another peripheral is specified the kernel will panic.
+    pub fn get_usart_clock_source(&self, usart: Peripheral) -> UsartClockSource {
+        self.cfgr3.get_usart_clock_source(usart)
+    }
+
+    /// Set the clock source of a USART. Only USART1, USART2 and USART3 have a selectable clock
+    /// source, if another peripheral is specified the kernel will panic.
+    pub fn set_usart_clock_source(&mut self, usart: Peripheral, source: UsartClockSource) {
+        self.cfgr3.set_usart_clock_source(usart, source);
+    }
+
+    /// Get the rate of the clock driving the specified peripheral.
+    ///
+    /// AHB peripherals run off of the AHB clock and APB peripherals off of the APB clock, except
+    /// for the timers, which run at twice the APB clock when the APB clock is divided, and the
+    /// USARTs that have had another clock source selected.
+    pub fn get_peripheral_clock_rate(&self, peripheral: Peripheral) -> u32 {
+        if self.cfgr3.serves_peripheral(peripheral) {
+            match self.cfgr3.get_usart_clock_source(peripheral) {
+                UsartClockSource::Pclk => self.get_pclk_rate(),
+                UsartClockSource::Sysclk => self.get_system_clock_rate(),
+                UsartClockSource::Lse => LSE_VALUE,
+                UsartClockSource::Hsi => HSI_VALUE,
+            }
+        }
+        else if self.ahbenr.serves_peripheral(peripheral) {
+            self.get_hclk_rate()
+        }
+        else if self.apbenr1.serves_peripheral(peripheral) ||
+                self.apbenr2.serves_peripheral(peripheral) {
+            match peripheral {
+                Peripheral::TIM1 | Peripheral::TIM2 | Peripheral::TIM3 | Peripheral::TIM6 |
+                Peripheral::TIM7 | Peripheral::TIM14 | Peripheral::TIM15 | Peripheral::TIM16 |
+                Peripheral::TIM17 if self.cfgr.get_apb_prescaler() != ApbPrescaler::Div1 => {
+                    self.get_pclk_rate() * 2
+                },
+                _ => self.get_pclk_rate(),
+            }
+        }
+        else {
+            panic!("RawRCC::get_peripheral_clock_rate - specified peripheral not served, did you
+            forget to add it to a control register?");
+        }
+    }
+
     /// Enable a peripheral
     pub fn enable_peripheral(&mut self, peripheral: Peripheral) {
         if self.ahbenr.serves_peripheral(peripheral) {
@@ -224,4 +471,38 @@ impl RawRCC {
             forget to add it to a control register?");
         }
     }
+
+    /// Reset the specified peripheral, returning all of its registers to their reset values.
+    ///
+    /// The CRC, flash interface, SRAM interface and DMA controllers have no reset line, so if one
+    /// of those is specified this method returns false. Otherwise, it returns true.
+    pub fn reset_peripheral(&mut self, peripheral: Peripheral) -> bool {
+        // UNSAFE: Each register is a field of this RCC. The reset bit is set and then cleared,
+        // both writes have to be volatile so that neither is optimized away
+        unsafe {
+            if self.ahbrstr.serves_peripheral(peripheral) {
+                update_volatile(&mut self.ahbrstr, |reg| reg.set_reset(true, peripheral));
+                update_volatile(&mut self.ahbrstr, |reg| reg.set_reset(false, peripheral));
+            }
+            else if self.apbrstr1.serves_peripheral(peripheral) {
+                update_volatile(&mut self.apbrstr1, |reg| reg.set_reset(true, peripheral));
+                update_volatile(&mut self.apbrstr1, |reg| reg.set_reset(false, peripheral));
+            }
+            else if self.apbrstr2.serves_peripheral(peripheral) {
+                update_volatile(&mut self.apbrstr2, |reg| reg.set_reset(true, peripheral));
+                update_volatile(&mut self.apbrstr2, |reg| reg.set_reset(false, peripheral));
+            }
+            else {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// Read a register, modify it and write it back with volatile accesses
+unsafe fn update_volatile<R, F: FnOnce(&mut R)>(reg: *mut R, modify: F) {
+    let mut value = ptr::read_volatile(reg);
+    modify(&mut value);
+    ptr::write_volatile(reg, value);
 }
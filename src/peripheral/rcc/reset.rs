@@ -0,0 +1,247 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module is used to control the AHBRSTR and APBRSTR registers, which hold peripherals in
+//! reset.
+
+use super::super::Field;
+use super::enable::Peripheral;
+
+#[derive(Copy, Clone, Debug)]
+pub struct AHBRSTR(u32);
+
+impl AHBRSTR {
+    pub fn get_reset(&self, peripheral: Peripheral) -> bool {
+        if !self.serves_peripheral(peripheral) {
+            panic!("AHBRSTR::get_reset - this register does not control the specified peripheral!");
+        }
+        let mask = peripheral.mask();
+
+        self.0 & mask != 0
+    }
+
+    pub fn set_reset(&mut self, reset: bool, peripheral: Peripheral) {
+        if !self.serves_peripheral(peripheral) {
+            panic!("AHBRSTR::set_reset - This register does not control the specified peripheral!");
+        }
+        let mask = peripheral.mask();
+
+        self.0 &= !mask;
+        if reset {
+            self.0 |= mask;
+        }
+    }
+
+    /// Only the GPIO groups and the touch sense controller can be reset on the AHB bus.
+    pub fn serves_peripheral(&self, peripheral: Peripheral) -> bool {
+        match peripheral {
+            Peripheral::TouchSenseController | Peripheral::GPIOA |
+            Peripheral::GPIOB | Peripheral::GPIOC | Peripheral::GPIOF => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct APBRSTR1(u32);
+
+impl APBRSTR1 {
+    pub fn get_reset(&self, peripheral: Peripheral) -> bool {
+        if !self.serves_peripheral(peripheral) {
+            panic!("APBRSTR1::get_reset - this register does not control the specified peripheral!");
+        }
+        let mask = peripheral.mask();
+
+        self.0 & mask != 0
+    }
+
+    pub fn set_reset(&mut self, reset: bool, peripheral: Peripheral) {
+        if !self.serves_peripheral(peripheral) {
+            panic!("APBRSTR1::set_reset - This register does not control the specified peripheral!");
+        }
+        let mask = peripheral.mask();
+
+        self.0 &= !mask;
+        if reset {
+            self.0 |= mask;
+        }
+    }
+
+    pub fn serves_peripheral(&self, peripheral: Peripheral) -> bool {
+        match peripheral {
+            Peripheral::CEC | Peripheral::DAC | Peripheral::PowerInterface |
+            Peripheral::ClockRecoverySystem | Peripheral::CAN | Peripheral::USB |
+            Peripheral::I2C1 | Peripheral::I2C2 | Peripheral::USART2 |
+            Peripheral::USART3 | Peripheral::USART4 | Peripheral::USART5 |
+            Peripheral::SPI2 | Peripheral::WindowWatchdog | Peripheral::TIM2 |
+            Peripheral::TIM3 | Peripheral::TIM6 | Peripheral::TIM7 | Peripheral::TIM14 => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct APBRSTR2(u32);
+
+impl APBRSTR2 {
+    pub fn get_reset(&self, peripheral: Peripheral) -> bool {
+        if !self.serves_peripheral(peripheral) {
+            panic!("APBRSTR2::get_reset - this register does not control the specified peripheral!");
+        }
+        let mask = peripheral.mask();
+
+        self.0 & mask != 0
+    }
+
+    pub fn set_reset(&mut self, reset: bool, peripheral: Peripheral) {
+        if !self.serves_peripheral(peripheral) {
+            panic!("APBRSTR2::set_reset - This register does not control the specified peripheral!");
+        }
+        let mask = peripheral.mask();
+
+        self.0 &= !mask;
+        if reset {
+            self.0 |= mask;
+        }
+    }
+
+    pub fn serves_peripheral(&self, peripheral: Peripheral) -> bool {
+        match peripheral {
+            Peripheral::MCUDebug | Peripheral::TIM1 | Peripheral::TIM15 |
+            Peripheral::TIM16 | Peripheral::TIM17 | Peripheral::USART1 |
+            Peripheral::USART6 | Peripheral::USART7 | Peripheral::USART8 |
+            Peripheral::SPI1 | Peripheral::ADC | Peripheral::SysCfgComp => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ahbrstr_get_reset() {
+        // GPIO Group A starts in reset
+        let ahbrstr = AHBRSTR(0b1 << 17);
+
+        assert_eq!(ahbrstr.get_reset(Peripheral::GPIOA), true);
+        assert_eq!(ahbrstr.get_reset(Peripheral::GPIOB), false);
+    }
+
+    #[test]
+    fn test_ahbrstr_set_reset_on() {
+        let mut ahbrstr = AHBRSTR(0);
+
+        ahbrstr.set_reset(true, Peripheral::TouchSenseController);
+        assert_eq!(ahbrstr.0, 0b1 << 24);
+    }
+
+    #[test]
+    fn test_ahbrstr_set_reset_off_doesnt_change_settings() {
+        // GPIOB and GPIOF start in reset
+        let mut ahbrstr = AHBRSTR(0b1 << 18 | 0b1 << 22);
+
+        ahbrstr.set_reset(false, Peripheral::GPIOF);
+        assert_eq!(ahbrstr.0, 0b1 << 18);
+    }
+
+    #[test]
+    fn test_ahbrstr_doesnt_serve_peripherals_without_reset() {
+        let ahbrstr = AHBRSTR(0);
+
+        assert!(!ahbrstr.serves_peripheral(Peripheral::DMA));
+        assert!(!ahbrstr.serves_peripheral(Peripheral::CRC));
+        assert!(!ahbrstr.serves_peripheral(Peripheral::SRAMInterface));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_ahbrstr_set_reset_unserved_peripheral_panics() {
+        let mut ahbrstr = AHBRSTR(0);
+
+        ahbrstr.set_reset(true, Peripheral::DMA);
+    }
+
+    #[test]
+    fn test_apbrstr1_get_reset() {
+        // I2C1 starts in reset
+        let apbrstr1 = APBRSTR1(0b1 << 21);
+
+        assert_eq!(apbrstr1.get_reset(Peripheral::I2C1), true);
+        assert_eq!(apbrstr1.get_reset(Peripheral::I2C2), false);
+    }
+
+    #[test]
+    fn test_apbrstr1_set_reset_on() {
+        let mut apbrstr1 = APBRSTR1(0);
+
+        apbrstr1.set_reset(true, Peripheral::I2C1);
+        assert_eq!(apbrstr1.0, 0b1 << 21);
+    }
+
+    #[test]
+    fn test_apbrstr1_set_reset_off_doesnt_change_settings() {
+        // USART2 and CRS start in reset
+        let mut apbrstr1 = APBRSTR1(0b1 << 17 | 0b1 << 27);
+
+        apbrstr1.set_reset(false, Peripheral::USART2);
+        assert_eq!(apbrstr1.0, 0b1 << 27);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apbrstr1_set_reset_unserved_peripheral_panics() {
+        let mut apbrstr1 = APBRSTR1(0);
+
+        apbrstr1.set_reset(true, Peripheral::GPIOA);
+    }
+
+    #[test]
+    fn test_apbrstr2_get_reset() {
+        // SPI1 starts in reset
+        let apbrstr2 = APBRSTR2(0b1 << 12);
+
+        assert_eq!(apbrstr2.get_reset(Peripheral::SPI1), true);
+        assert_eq!(apbrstr2.get_reset(Peripheral::USART1), false);
+    }
+
+    #[test]
+    fn test_apbrstr2_set_reset_on() {
+        let mut apbrstr2 = APBRSTR2(0);
+
+        apbrstr2.set_reset(true, Peripheral::USART1);
+        assert_eq!(apbrstr2.0, 0b1 << 14);
+    }
+
+    #[test]
+    fn test_apbrstr2_set_reset_off_doesnt_change_settings() {
+        // TIM1 and ADC start in reset
+        let mut apbrstr2 = APBRSTR2(0b1 << 11 | 0b1 << 9);
+
+        apbrstr2.set_reset(false, Peripheral::TIM1);
+        assert_eq!(apbrstr2.0, 0b1 << 9);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_apbrstr2_set_reset_unserved_peripheral_panics() {
+        let mut apbrstr2 = APBRSTR2(0);
+
+        apbrstr2.set_reset(true, Peripheral::USB);
+    }
+}
@@ -33,10 +33,41 @@ pub enum BaudRate {
     Hz115200,
 }
 
+impl BaudRate {
+    /// The baud rate in Hz.
+    pub fn hz(&self) -> u32 {
+        match *self {
+            BaudRate::Hz4800 => 4_800,
+            BaudRate::Hz9600 => 9_600,
+            BaudRate::Hz19200 => 19_200,
+            BaudRate::Hz57600 => 57_600,
+            BaudRate::Hz115200 => 115_200,
+        }
+    }
+}
+
+/// Compute USARTDIV for the baud rate, rounded to the nearest value.
+///
+/// With oversampling by 16 USARTDIV = fck / baud, with oversampling by 8 it is 2 * fck / baud.
+pub fn usart_div(baud_rate: u32, clock_rate: u32, over8: bool) -> u32 {
+    let clock_rate = if over8 { clock_rate * 2 } else { clock_rate };
+    (clock_rate + baud_rate / 2) / baud_rate
+}
+
+/// The baud rate actually produced by a USARTDIV value.
+pub fn actual_baud_rate(div: u32, clock_rate: u32, over8: bool) -> u32 {
+    let clock_rate = if over8 { clock_rate * 2 } else { clock_rate };
+    (clock_rate + div / 2) / div
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct BRR(u32);
 
 impl BRR {
+    pub fn set_baud_rate(&mut self, baud_rate: BaudRate, clock_rate: u32, over8: bool) {
+        self.set_div(usart_div(baud_rate.hz(), clock_rate, over8), over8);
+    }
+
     /* Bits 31:16 Reserved, must be kept at reset value.
      * Bits 15:4 BRR[15:4]
      *   BRR[15:4] = USARTDIV[15:4]
@@ -46,14 +77,8 @@ impl BRR {
      *   BRR[2:0] = USARTDIV[3:0] shifted 1 bit to the right.
      *   BRR[3] must be kept cleared.
      */
-    pub fn set_baud_rate(&mut self, baud_rate: BaudRate, clock_rate: u32, over8: bool) {
-        let mut rate = match baud_rate {
-            BaudRate::Hz4800 => clock_rate/4_800,
-            BaudRate::Hz9600 => clock_rate/9_600,
-            BaudRate::Hz19200 => clock_rate/19_200,
-            BaudRate::Hz57600 => clock_rate/57_600,
-            BaudRate::Hz115200 => clock_rate/115_200,
-        };
+    pub fn set_div(&mut self, div: u32, over8: bool) {
+        let mut rate = div & BRR_MASK;
 
         if over8 {
             let mut low_bits = rate & DIV_MASK;
@@ -65,3 +90,51 @@ impl BRR {
         self.0 = rate;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usart_div_rounds_to_nearest() {
+        // 48 MHz / 115200 = 416.67
+        assert_eq!(usart_div(115_200, 48_000_000, false), 417);
+        // 8 MHz / 9600 = 833.33
+        assert_eq!(usart_div(9_600, 8_000_000, false), 833);
+    }
+
+    #[test]
+    fn test_usart_div_over8_doubles_clock() {
+        assert_eq!(usart_div(115_200, 48_000_000, true), 833);
+    }
+
+    #[test]
+    fn test_actual_baud_rate() {
+        assert_eq!(actual_baud_rate(417, 48_000_000, false), 115_108);
+        assert_eq!(actual_baud_rate(833, 48_000_000, true), 115_246);
+    }
+
+    #[test]
+    fn test_brr_set_div_over16() {
+        let mut brr = BRR(0);
+        brr.set_div(417, false);
+        assert_eq!(brr.0, 417);
+    }
+
+    #[test]
+    fn test_brr_set_div_over8_shifts_low_bits() {
+        let mut brr = BRR(0);
+        // 833 = 0x341, low nibble shifted right becomes 0x0
+        brr.set_div(0x341, true);
+        assert_eq!(brr.0, 0x340);
+        brr.set_div(0x34F, true);
+        assert_eq!(brr.0, 0x347);
+    }
+
+    #[test]
+    fn test_brr_set_baud_rate() {
+        let mut brr = BRR(0);
+        brr.set_baud_rate(BaudRate::Hz9600, 8_000_000, false);
+        assert_eq!(brr.0, 833);
+    }
+}
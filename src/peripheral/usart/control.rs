@@ -23,7 +23,7 @@
 use super::defs::*;
 
 /// Defines the possible HardwareFlowControl configurations for the Usart.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum HardwareFlowControl {
     /// No hardware flow control.
     None,
@@ -157,6 +157,13 @@ impl CR1 {
         }
     }
 
+    /* Checks if the RXNE interrupt is enabled.
+     * Returns true if enabled (CR1 bit 5 (RXNEIE) = 1), false otherwise.
+     */
+    pub fn is_receiver_not_empty_interrupt_enabled(&self) -> bool {
+        self.0 & CR1_RXNEIE != 0
+    }
+
     /* Uses bit 6 in CR1 to enable or disable the TCIE interrupt based on the
      * bool variable passed in.
      *      true: Enables interrupt
@@ -192,6 +199,20 @@ impl CR1 {
         }
     }
 
+    /* Checks if the TXE interrupt is enabled.
+     * Returns true if enabled (CR1 bit 7 (TXEIE) = 1), false otherwise.
+     */
+    pub fn is_transmit_interrupt_enabled(&self) -> bool {
+        self.0 & CR1_TXEIE != 0
+    }
+
+    /* Checks if the TC interrupt is enabled.
+     * Returns true if enabled (CR1 bit 6 (TCIE) = 1), false otherwise.
+     */
+    pub fn is_transmit_complete_interrupt_enabled(&self) -> bool {
+        self.0 & CR1_TCIE != 0
+    }
+
     /* Uses bits 9 and 10 in CR1 to set the parity to None, Even, Odd
      *  Bit 9 PS: Parity selection
      *      This bit selects the odd or even parity when the parity
@@ -345,6 +366,31 @@ impl CR3 {
         self.0 &= !(CR3_RTSE | CR3_CTSE);
         self.0 |= mask;
     }
+
+    /* Returns the hardware flow control configuration from bits 8 and 9.
+     */
+    pub fn get_hardware_flow_control(&self) -> HardwareFlowControl {
+        match (self.0 & CR3_RTSE != 0, self.0 & CR3_CTSE != 0) {
+            (false, false) => HardwareFlowControl::None,
+            (true, false) => HardwareFlowControl::Rts,
+            (false, true) => HardwareFlowControl::Cts,
+            (true, true) => HardwareFlowControl::All,
+        }
+    }
+
+    /* Uses bit 10 in CR3 to enable or disable the CTS interrupt based on the
+     * bool variable passed in.
+     *      Bit 10 CTSIE: CTS interrupt enable
+     *          0: Interrupt is inhibited
+     *          1: An interrupt is generated whenever CTSIF=1 in the
+     *          USARTx_ISR register
+     */
+    pub fn set_cts_interrupt(&mut self, enable: bool) {
+        self.0 &= !(CR3_CTSIE);
+        if enable {
+            self.0 |= CR3_CTSIE;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -456,6 +502,14 @@ mod tests {
         assert_eq!(cr1.0, 0);
     }
 
+    #[test]
+    fn test_cr1_is_transmit_interrupt_enabled() {
+        let mut cr1 = CR1(0);
+        assert!(!cr1.is_transmit_interrupt_enabled());
+        cr1.set_transmit_interrupt(true);
+        assert!(cr1.is_transmit_interrupt_enabled());
+    }
+
     #[test]
     fn test_cr1_enable_transmit_complete_interrupt() {
         let mut cr1 = CR1(0);
@@ -463,6 +517,14 @@ mod tests {
         assert_eq!(cr1.0, 0b1 << 6);
     }
 
+    #[test]
+    fn test_cr1_is_transmit_complete_interrupt_enabled() {
+        let mut cr1 = CR1(0);
+        assert!(!cr1.is_transmit_complete_interrupt_enabled());
+        cr1.set_transmit_complete_interrupt(true);
+        assert!(cr1.is_transmit_complete_interrupt_enabled());
+    }
+
     #[test]
     fn test_cr1_disable_transmit_complete_interrupt() {
         let mut cr1 = CR1(0b1 << 6);
@@ -536,4 +598,30 @@ mod tests {
         cr3.set_hardware_flow_control(HardwareFlowControl::None);
         assert_eq!(cr3.0, 0b0);
     }
+
+    #[test]
+    fn test_cr3_get_hardware_flow_control() {
+        let mut cr3 = CR3(0);
+        assert_eq!(cr3.get_hardware_flow_control(), HardwareFlowControl::None);
+
+        cr3.set_hardware_flow_control(HardwareFlowControl::Rts);
+        assert_eq!(cr3.get_hardware_flow_control(), HardwareFlowControl::Rts);
+
+        cr3.set_hardware_flow_control(HardwareFlowControl::Cts);
+        assert_eq!(cr3.get_hardware_flow_control(), HardwareFlowControl::Cts);
+
+        cr3.set_hardware_flow_control(HardwareFlowControl::All);
+        assert_eq!(cr3.get_hardware_flow_control(), HardwareFlowControl::All);
+    }
+
+    #[test]
+    fn test_cr3_set_cts_interrupt() {
+        let mut cr3 = CR3(0);
+
+        cr3.set_cts_interrupt(true);
+        assert_eq!(cr3.0, 0b1 << 10);
+
+        cr3.set_cts_interrupt(false);
+        assert_eq!(cr3.0, 0b0);
+    }
 }
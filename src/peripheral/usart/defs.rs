@@ -24,9 +24,31 @@
 
 #![allow(missing_docs)]
 
-// Base addresses for USART 1 and 2
+// Base addresses for USART 1 through 8
 pub const USART1_ADDR: *const u32 = 0x4001_3800 as *const _;
 pub const USART2_ADDR: *const u32 = 0x4000_4400 as *const _;
+#[cfg(feature="stm32f09x")]
+pub const USART3_ADDR: *const u32 = 0x4000_4800 as *const _;
+#[cfg(feature="stm32f09x")]
+pub const USART4_ADDR: *const u32 = 0x4000_4C00 as *const _;
+#[cfg(feature="stm32f09x")]
+pub const USART5_ADDR: *const u32 = 0x4000_5000 as *const _;
+#[cfg(feature="stm32f09x")]
+pub const USART6_ADDR: *const u32 = 0x4001_1400 as *const _;
+#[cfg(feature="stm32f09x")]
+pub const USART7_ADDR: *const u32 = 0x4001_1800 as *const _;
+#[cfg(feature="stm32f09x")]
+pub const USART8_ADDR: *const u32 = 0x4001_1C00 as *const _;
+
+// Number of USART instances on the chip
+#[cfg(not(feature="stm32f09x"))]
+pub const USART_COUNT: usize = 2;
+#[cfg(feature="stm32f09x")]
+pub const USART_COUNT: usize = 8;
+
+// Sleep/wake channels for each USART, offset by the instance index
+pub const USART_TX_CHAN_BASE: usize = 42;
+pub const USART_RX_CHAN_FACTOR: usize = 3;
 
 // ------------------------------------
 // USARTx - CR1 Bit definitions
@@ -90,12 +112,19 @@ pub const CR3_DMAR:   u32 = 0b1 << 6;
 pub const CR3_DMAT:   u32 = 0b1 << 7;
 pub const CR3_RTSE:   u32 = 0b1 << 8;
 pub const CR3_CTSE:   u32 = 0b1 << 9;
+pub const CR3_CTSIE:  u32 = 0b1 << 10;
 
 // ------------------------------------
 // USARTx - BRR bit definitions
 // ------------------------------------
 pub const BRR_OFFSET: u32 = 0x0C;
 pub const DIV_MASK: u32   = 0b1111;
+pub const BRR_MASK: u32   = 0xFFFF;
+// USARTDIV must be at least 16 and fit in the 16 bits of the BRR
+pub const USARTDIV_MIN: u32 = 16;
+pub const USARTDIV_MAX: u32 = 0xFFFF;
+// Largest baud rate error accepted when configuring a Usart, in parts per thousand
+pub const BAUD_ERROR_MAX_PERMILLE: u32 = 20;
 
 // ------------------------------------
 // USARTx - GTPR bit definitions
@@ -114,6 +143,9 @@ pub const ISR_IDLE: u32   = 0b1 << 4;
 pub const ISR_RXNE: u32   = 0b1 << 5;
 pub const ISR_TC: u32     = 0b1 << 6;
 pub const ISR_TXE: u32    = 0b1 << 7;
+pub const ISR_LBDF: u32   = 0b1 << 8;
+pub const ISR_CTSIF: u32  = 0b1 << 9;
+pub const ISR_CTS: u32    = 0b1 << 10;
 
 // ------------------------------------
 // USARTx - ICR bit definitions
@@ -27,6 +27,27 @@ use super::defs::*;
 pub struct ICR(u32);
 
 impl ICR {
+    /*  Bit 0 PECF: Parity error clear flag
+     *  Writing 1 to this bit clears the PE flag in the USARTx_ISR.
+     */
+    pub fn clear_pe(&mut self) {
+        self.0 |= ICR_PECF;
+    }
+
+    /*  Bit 1 FECF: Framing error clear flag
+     *  Writing 1 to this bit clears the FE flag in the USARTx_ISR.
+     */
+    pub fn clear_fe(&mut self) {
+        self.0 |= ICR_FECF;
+    }
+
+    /*  Bit 2 NCF: Noise detected clear flag
+     *  Writing 1 to this bit clears the NF flag in the USARTx_ISR.
+     */
+    pub fn clear_nf(&mut self) {
+        self.0 |= ICR_NCF;
+    }
+
     /*  Bit 3 ORECF: Overrun error clear flag
      *  Writing 1 to this bit clears the ORE flag in the USARTx_ISR.
      */
@@ -47,12 +68,43 @@ impl ICR {
     pub fn clear_tc(&mut self) {
         self.0 |= ICR_TCCF;
     }
+
+    /* Bit 9 CTSCF: CTS clear flag
+     * Writing 1 to this bit clears the CTSIF flag in the USARTx_ISR.
+     */
+    pub fn clear_cts(&mut self) {
+        self.0 |= ICR_CTSCF;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_icr_clear_pe() {
+        let mut icr = ICR(0);
+        icr.clear_pe();
+
+        assert_eq!(icr.0, 0b1);
+    }
+
+    #[test]
+    fn test_icr_clear_fe() {
+        let mut icr = ICR(0);
+        icr.clear_fe();
+
+        assert_eq!(icr.0, 0b1 << 1);
+    }
+
+    #[test]
+    fn test_icr_clear_nf() {
+        let mut icr = ICR(0);
+        icr.clear_nf();
+
+        assert_eq!(icr.0, 0b1 << 2);
+    }
+
     #[test]
     fn test_icr_clear_ore() {
         let mut icr = ICR(0);
@@ -68,4 +120,12 @@ mod tests {
 
         assert_eq!(icr.0, 0b1 << 6);
     }
+
+    #[test]
+    fn test_icr_clear_cts() {
+        let mut icr = ICR(0);
+        icr.clear_cts();
+
+        assert_eq!(icr.0, 0b1 << 9);
+    }
 }
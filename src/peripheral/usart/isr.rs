@@ -26,6 +26,52 @@ use super::defs::*;
 pub struct ISR(u32);
 
 impl ISR {
+    /* Bit 0 PE: Parity error
+     *   This bit is set by hardware when a parity error occurs in receiver
+     *   mode. It is cleared by software, writing 1 to the PECF in the
+     *   USARTx_ICR. An interrupt is generated if PEIE = 1 in the USARTx_CR1.
+     *      0: No parity error
+     *      1: Parity error
+     */
+    pub fn get_pe(&self) -> bool {
+        self.0 & ISR_PE != 0
+    }
+
+    /* Bit 1 FE: Framing error
+     *   This bit is set by hardware when a de-synchronization, excessive noise
+     *   or a break character is detected. It is cleared by software, writing
+     *   1 to the FECF bit in the USARTx_ICR.
+     *      0: No Framing error is detected
+     *      1: Framing error or break character is detected
+     */
+    pub fn get_fe(&self) -> bool {
+        self.0 & ISR_FE != 0
+    }
+
+    /* Bit 2 NF: START bit Noise detection flag
+     *   This bit is set by hardware when noise is detected on a received
+     *   frame. It is cleared by software, writing 1 to the NFCF bit in the
+     *   USARTx_ICR.
+     *      0: No noise is detected
+     *      1: Noise is detected
+     */
+    pub fn get_nf(&self) -> bool {
+        self.0 & ISR_NF != 0
+    }
+
+    /* Bit 3 ORE: Overrun error
+     *   This bit is set by hardware when the data currently being received in
+     *   the shift register is ready to be transferred into the RDR register
+     *   while RXNE=1. It is cleared by a software, writing 1 to the ORECF, in
+     *   the USARTx_ICR. An interrupt is generated if RXNEIE=1 in the
+     *   USARTx_CR1.
+     *      0: No overrun error
+     *      1: Overrun error is detected
+     */
+    pub fn get_ore(&self) -> bool {
+        self.0 & ISR_ORE != 0
+    }
+
     /* Bit 5 RXNE: Read data register not empty
      *   This bit is set by hardware when the content of the RDR shift register
      *   has been transferred to the USARTx_RDR. It is cleared by a
@@ -70,12 +116,69 @@ impl ISR {
     pub fn get_txe(&self) -> bool {
         self.0 & ISR_TXE != 0
     }
+
+    /* Bit 9 CTSIF: CTS interrupt flag
+     *   This bit is set by hardware when the nCTS input toggles, if the CTSE
+     *   bit is set. It is cleared by software, by writing 1 to the CTSCF bit
+     *   in the USARTx_ICR. An interrupt is generated if CTSIE=1 in the
+     *   USARTx_CR3.
+     *      0: No change occurred on the nCTS status line
+     *      1: A change occurred on the nCTS status line
+     */
+    pub fn get_ctsif(&self) -> bool {
+        self.0 & ISR_CTSIF != 0
+    }
+
+    /* Bit 10 CTS: CTS flag
+     *   This bit is set/reset by hardware. It is an inverted copy of the
+     *   status of the nCTS input pin.
+     *      0: nCTS line set
+     *      1: nCTS line reset
+     */
+    pub fn get_cts(&self) -> bool {
+        self.0 & ISR_CTS != 0
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_isr_get_error_flags_return_false_when_bits_not_set() {
+        let isr = ISR(0);
+        assert_eq!(isr.get_pe(), false);
+        assert_eq!(isr.get_fe(), false);
+        assert_eq!(isr.get_nf(), false);
+        assert_eq!(isr.get_ore(), false);
+    }
+
+    #[test]
+    fn test_isr_get_pe_returns_true_when_bit_is_set() {
+        let isr = ISR(0b1);
+        assert_eq!(isr.get_pe(), true);
+        assert_eq!(isr.get_fe(), false);
+    }
+
+    #[test]
+    fn test_isr_get_fe_returns_true_when_bit_is_set() {
+        let isr = ISR(0b1 << 1);
+        assert_eq!(isr.get_fe(), true);
+        assert_eq!(isr.get_pe(), false);
+    }
+
+    #[test]
+    fn test_isr_get_nf_returns_true_when_bit_is_set() {
+        let isr = ISR(0b1 << 2);
+        assert_eq!(isr.get_nf(), true);
+    }
+
+    #[test]
+    fn test_isr_get_ore_returns_true_when_bit_is_set() {
+        let isr = ISR(0b1 << 3);
+        assert_eq!(isr.get_ore(), true);
+    }
+
     #[test]
     fn test_isr_get_rxne_returns_false_when_bit_not_set() {
         let isr = ISR(0);
@@ -111,4 +214,18 @@ mod tests {
         let isr = ISR(0b1 << 7);
         assert_eq!(isr.get_txe(), true);
     }
+
+    #[test]
+    fn test_isr_get_ctsif_returns_true_when_bit_is_set() {
+        let isr = ISR(0b1 << 9);
+        assert_eq!(isr.get_ctsif(), true);
+        assert_eq!(isr.get_cts(), false);
+    }
+
+    #[test]
+    fn test_isr_get_cts_returns_true_when_bit_is_set() {
+        let isr = ISR(0b1 << 10);
+        assert_eq!(isr.get_cts(), true);
+        assert_eq!(isr.get_ctsif(), false);
+    }
 }
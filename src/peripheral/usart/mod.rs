@@ -18,7 +18,7 @@
 //! This module is the highest level in the Usart hierarchy for implementing
 //! the serial driver.
 //!
-//! Configuration for each of the two Usart registers, and each of the registers
+//! Configuration for each of the Usart peripherals, and each of the registers
 //! that are offset from Usartx, and the public functions used to initialize,
 //! configure, and manipulate the bits for each register are defined in this file.
 //!
@@ -26,8 +26,8 @@
 //! each necessary level (one or more), until the actual register is reached
 //! and is able to set the bits for itself accordingly.
 //!
-//! This module is also responsible for initial setup of the Usart register
-//! (Either Usart1 or Usart2).
+//! This module is also responsible for initial setup of the Usart peripherals,
+//! including the one the board uses for its console.
 
 pub mod defs;
 
@@ -47,25 +47,196 @@ use self::rdr::RDR;
 use self::isr::ISR;
 use self::icr::ICR;
 use self::defs::*;
+use altos_core::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
 use peripheral::{rcc, gpio};
-use interrupt;
+use interrupt::{self, Hardware};
+use peripheral::gpio::af::{SignalPin, UsartTx, UsartRx};
+#[cfg(feature="serial")]
+use board::Board;
 
 pub use self::control::{WordLength, Mode, Parity, StopLength, HardwareFlowControl, DMAMode};
-pub use self::baudr::BaudRate;
+pub use self::baudr::{BaudRate, usart_div, actual_baud_rate};
 
-/// Defines the wake/sleep channel for the TX buffer when full.
-pub const USART2_TX_CHAN: usize = 43;
-/// Defines the wake/sleep channel for when bytes are available in the receive buffer.
-pub const USART2_RX_CHAN: usize = 43 * 3;
+/// Defines the wake/sleep channel for the Usart2 TX buffer when full.
+pub const USART2_TX_CHAN: usize = USART_TX_CHAN_BASE + 1;
+/// Defines the wake/sleep channel for when bytes are available in the Usart2 receive buffer.
+pub const USART2_RX_CHAN: usize = USART2_TX_CHAN * USART_RX_CHAN_FACTOR;
 
-/// STM32F0 has two Usart registers available.
-#[derive(Copy, Clone, Debug)]
+/// The Usart peripherals the chip has.
+///
+/// The STM32F042 only has Usart1 and Usart2, the `stm32f09x` feature adds Usart3 through Usart8
+/// for the STM32F09x chips.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum UsartX {
     /// Connected to PA9 (TX) and PA10 (RX).
     Usart1,
     /// Usart2 is the debug serial.
     /// Connected to PA2 (TX) and pa15 (RX).
     Usart2,
+    /// Shares its interrupt with Usart4 through Usart8.
+    #[cfg(feature="stm32f09x")]
+    Usart3,
+    /// Shares its interrupt with Usart3 and Usart5 through Usart8.
+    #[cfg(feature="stm32f09x")]
+    Usart4,
+    /// Shares its interrupt with Usart3, Usart4 and Usart6 through Usart8.
+    #[cfg(feature="stm32f09x")]
+    Usart5,
+    /// Shares its interrupt with Usart3 through Usart5, Usart7 and Usart8.
+    #[cfg(feature="stm32f09x")]
+    Usart6,
+    /// Shares its interrupt with Usart3 through Usart6 and Usart8.
+    #[cfg(feature="stm32f09x")]
+    Usart7,
+    /// Shares its interrupt with Usart3 through Usart7.
+    #[cfg(feature="stm32f09x")]
+    Usart8,
+}
+
+#[cfg(not(feature="stm32f09x"))]
+static USARTS: [UsartX; USART_COUNT] = [
+    UsartX::Usart1,
+    UsartX::Usart2,
+];
+
+#[cfg(feature="stm32f09x")]
+static USARTS: [UsartX; USART_COUNT] = [
+    UsartX::Usart1,
+    UsartX::Usart2,
+    UsartX::Usart3,
+    UsartX::Usart4,
+    UsartX::Usart5,
+    UsartX::Usart6,
+    UsartX::Usart7,
+    UsartX::Usart8,
+];
+
+impl UsartX {
+    /// Every Usart peripheral, in order.
+    pub fn all() -> &'static [UsartX] {
+        &USARTS
+    }
+
+    /// The zero based index of this Usart, Usart1 is 0.
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// The RCC peripheral for this USART.
+    pub fn peripheral(&self) -> rcc::Peripheral {
+        match *self {
+            UsartX::Usart1 => rcc::Peripheral::USART1,
+            UsartX::Usart2 => rcc::Peripheral::USART2,
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart3 => rcc::Peripheral::USART3,
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart4 => rcc::Peripheral::USART4,
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart5 => rcc::Peripheral::USART5,
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart6 => rcc::Peripheral::USART6,
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart7 => rcc::Peripheral::USART7,
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart8 => rcc::Peripheral::USART8,
+        }
+    }
+
+    /// The interrupt for this USART. Usart3 through Usart8 share a single interrupt.
+    pub fn hardware(&self) -> Hardware {
+        match *self {
+            UsartX::Usart1 => Hardware::Usart1,
+            UsartX::Usart2 => Hardware::Usart2,
+            #[cfg(feature="stm32f09x")]
+            _ => Hardware::Usart3Plus,
+        }
+    }
+
+    /// The wake/sleep channel for this Usart's TX buffer when full.
+    pub fn tx_chan(&self) -> usize {
+        USART_TX_CHAN_BASE + self.index()
+    }
+
+    /// The wake/sleep channel for when bytes are available in this Usart's receive buffer.
+    pub fn rx_chan(&self) -> usize {
+        self.tx_chan() * USART_RX_CHAN_FACTOR
+    }
+}
+
+/// A Usart known at compile time, so pins can be tied to the Usart they carry signals for.
+///
+/// Only the Usarts the chip has are given a type, so the others can't be opened.
+pub trait UsartId {
+    /// The Usart this type represents.
+    fn usart() -> UsartX;
+}
+
+/// Usart1, see `UsartId`.
+pub struct Usart1;
+/// Usart2, see `UsartId`.
+pub struct Usart2;
+
+impl UsartId for Usart1 {
+    fn usart() -> UsartX { UsartX::Usart1 }
+}
+
+impl UsartId for Usart2 {
+    fn usart() -> UsartX { UsartX::Usart2 }
+}
+
+macro_rules! f09x_usart_ids {
+    ($($usart:ident,)*) => {
+        $(
+            #[doc = "A Usart only the STM32F09x chips have, see `UsartId`."]
+            #[cfg(feature="stm32f09x")]
+            pub struct $usart;
+
+            #[cfg(feature="stm32f09x")]
+            impl UsartId for $usart {
+                fn usart() -> UsartX { UsartX::$usart }
+            }
+        )*
+    }
+}
+
+f09x_usart_ids! {
+    Usart3, Usart4, Usart5, Usart6, Usart7, Usart8,
+}
+
+// The USART the console runs on, stored as its index plus one. Zero means `init` has not run
+// yet, in which case the console defaults to Usart2.
+static CONSOLE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+// A bit for each Usart that has been opened, indexed by `UsartX::index`.
+static OPEN: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Errors that can occur when opening a Usart.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UsartError {
+    /// The Usart has already been opened, either by `open` or by the kernel for the console.
+    AlreadyOpen(UsartX),
+}
+
+/// Get the USART the console runs on.
+pub fn console_usart() -> UsartX {
+    match CONSOLE.load(Ordering::Relaxed) {
+        0 => UsartX::Usart2,
+        n => USARTS[n - 1],
+    }
+}
+
+/// Get the USART the console runs on.
+///
+/// This is unsafe because the console is owned by the kernel, it should be written to with the
+/// `io` functions instead.
+#[doc(hidden)]
+pub unsafe fn console() -> Usart {
+    Usart::new(console_usart())
+}
+
+/// Check if the Usart has been opened with `open`.
+pub fn is_open(x: UsartX) -> bool {
+    OPEN.load(Ordering::Relaxed) & (0b1 << x.index()) != 0
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -87,18 +258,32 @@ pub struct RawUsart {
 
 /// Usart is the serial peripheral. This struct is used to configure
 /// the serial peripheral to send and receive data through the serial bus.
-#[derive(Copy, Clone, Debug)]
+#[derive(Debug)]
 pub struct Usart(Volatile<RawUsart>);
 
 impl Usart {
     /// Creates a new Usart object to configure the specifications for
     /// the serial peripheral.
-    pub fn new(x: UsartX) -> Self {
-        unsafe {
-            match x {
-                UsartX::Usart1 => Usart(Volatile::new(USART1_ADDR as *const _)),
-                UsartX::Usart2 => Usart(Volatile::new(USART2_ADDR as *const _)),
-            }
+    ///
+    /// This is unsafe because nothing stops the same Usart from being created twice,
+    /// application code should get a Usart from `open` instead.
+    #[doc(hidden)]
+    pub unsafe fn new(x: UsartX) -> Self {
+        match x {
+            UsartX::Usart1 => Usart(Volatile::new(USART1_ADDR as *const _)),
+            UsartX::Usart2 => Usart(Volatile::new(USART2_ADDR as *const _)),
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart3 => Usart(Volatile::new(USART3_ADDR as *const _)),
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart4 => Usart(Volatile::new(USART4_ADDR as *const _)),
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart5 => Usart(Volatile::new(USART5_ADDR as *const _)),
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart6 => Usart(Volatile::new(USART6_ADDR as *const _)),
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart7 => Usart(Volatile::new(USART7_ADDR as *const _)),
+            #[cfg(feature="stm32f09x")]
+            UsartX::Usart8 => Usart(Volatile::new(USART8_ADDR as *const _)),
         }
     }
 }
@@ -138,6 +323,12 @@ impl RawUsart {
         self.cr1.set_mode(mode);
     }
 
+    /// Check if the RXNE interrupt is enabled. Returns true if enabled,
+    /// false otherwise.
+    pub fn is_receiver_not_empty_interrupt_enabled(&self) -> bool {
+        self.cr1.is_receiver_not_empty_interrupt_enabled()
+    }
+
     /// Enable the RXNE interrupt. This interrupt occurs when the
     /// receive data register has data in it.
     pub fn enable_receiver_not_empty_interrupt(&mut self) {
@@ -174,6 +365,18 @@ impl RawUsart {
         self.cr1.set_transmit_interrupt(false);
     }
 
+    /// Check if the TXE interrupt is enabled. Returns true if enabled,
+    /// false otherwise.
+    pub fn is_transmit_interrupt_enabled(&self) -> bool {
+        self.cr1.is_transmit_interrupt_enabled()
+    }
+
+    /// Check if the TC interrupt is enabled. Returns true if enabled,
+    /// false otherwise.
+    pub fn is_transmit_complete_interrupt_enabled(&self) -> bool {
+        self.cr1.is_transmit_complete_interrupt_enabled()
+    }
+
     /// Enables parity checking. Used to determine if data corruption
     /// has occurred.
     pub fn set_parity(&mut self, parity: Parity) {
@@ -195,6 +398,11 @@ impl RawUsart {
         self.cr1.set_over8(false);
     }
 
+    /// Check if oversampling by 8 is enabled.
+    pub fn is_over8_enabled(&self) -> bool {
+        self.cr1.get_over8()
+    }
+
     /// Set the number of stop bits.
     pub fn set_stop_bits(&mut self, length: StopLength) {
         self.cr2.set_stop_bits(length);
@@ -205,15 +413,33 @@ impl RawUsart {
         self.cr3.set_dma_mode(dma_mode);
     }
 
-    /// Set hardware flow control mode.
+    /// Set hardware flow control mode. The Usart must be disabled.
     ///
-    /// # Note
-    ///
-    /// Implementation for this functionality is not complete.
+    /// With RTS enabled nRTS is deasserted while the RDR holds a byte that
+    /// hasn't been read. With CTS enabled a byte is only transmitted while
+    /// nCTS is asserted. The nRTS and nCTS pins are connected with
+    /// `connect_pin`.
     pub fn set_hardware_flow_control(&mut self, hfc: HardwareFlowControl) {
         self.cr3.set_hardware_flow_control(hfc);
     }
 
+    /// Get hardware flow control mode.
+    pub fn get_hardware_flow_control(&self) -> HardwareFlowControl {
+        self.cr3.get_hardware_flow_control()
+    }
+
+    /// Enable the CTS interrupt. This interrupt occurs when the nCTS
+    /// input toggles.
+    pub fn enable_cts_interrupt(&mut self) {
+        self.cr3.set_cts_interrupt(true);
+    }
+
+    /// Disable the CTS interrupt. This interrupt occurs when the nCTS
+    /// input toggles.
+    pub fn disable_cts_interrupt(&mut self) {
+        self.cr3.set_cts_interrupt(false);
+    }
+
     // --------------------------------------------------------------
 
     /// Set baud rate based on clock rate function argument.
@@ -221,6 +447,12 @@ impl RawUsart {
         self.brr.set_baud_rate(baud_rate, clock_rate, self.cr1.get_over8());
     }
 
+    /// Set the baud rate divider directly, see `usart_div`. The divider is
+    /// interpreted according to the current oversampling mode.
+    pub fn set_baud_divider(&mut self, div: u32) {
+        self.brr.set_div(div, self.cr1.get_over8());
+    }
+
     // --------------------------------------------------------------
 
     /// Move byte to TDR in order to transmit it.
@@ -256,8 +488,63 @@ impl RawUsart {
         self.isr.get_txe()
     }
 
+    /// Check if CTSIF flag is set. CTSIF flag is set when the nCTS input
+    /// toggles. Returns true if CTSIF flag is set, false otherwise.
+    pub fn is_cts_changed(&self) -> bool {
+        self.isr.get_ctsif()
+    }
+
+    /// Check if the nCTS input is asserted (low). Returns true if the peer
+    /// is ready to receive, false otherwise.
+    pub fn is_cts_asserted(&self) -> bool {
+        self.isr.get_cts()
+    }
+
+    /// Check if PE flag is set. PE flag is set when a parity error
+    /// occurs while receiving. Returns true if PE flag is set, false otherwise.
+    pub fn is_parity_error(&self) -> bool {
+        self.isr.get_pe()
+    }
+
+    /// Check if FE flag is set. FE flag is set when a framing error or a
+    /// break character is detected while receiving. Returns true if FE flag
+    /// is set, false otherwise.
+    pub fn is_framing_error(&self) -> bool {
+        self.isr.get_fe()
+    }
+
+    /// Check if NF flag is set. NF flag is set when noise is detected on a
+    /// received frame. Returns true if NF flag is set, false otherwise.
+    pub fn is_noise_detected(&self) -> bool {
+        self.isr.get_nf()
+    }
+
+    /// Check if ORE flag is set. ORE flag is set when data is received
+    /// when the RDR is full. Returns true if ORE flag is set, false otherwise.
+    pub fn is_overrun(&self) -> bool {
+        self.isr.get_ore()
+    }
+
     // --------------------------------------------------------------
 
+    /// Clear the PE flag. PE flag is set when a parity error occurs while
+    /// receiving.
+    pub fn clear_pe_flag(&mut self) {
+        self.icr.clear_pe();
+    }
+
+    /// Clear the FE flag. FE flag is set when a framing error or a break
+    /// character is detected while receiving.
+    pub fn clear_fe_flag(&mut self) {
+        self.icr.clear_fe();
+    }
+
+    /// Clear the NF flag. NF flag is set when noise is detected on a
+    /// received frame.
+    pub fn clear_nf_flag(&mut self) {
+        self.icr.clear_nf();
+    }
+
     /// Clear the ORE flag. ORE flag is set when data is received when
     /// the RDR is full.
     pub fn clear_ore_flag(&mut self) {
@@ -270,50 +557,142 @@ impl RawUsart {
         self.icr.clear_tc();
     }
 
+    /// Clear the CTSIF flag. CTSIF flag is set when the nCTS input toggles.
+    pub fn clear_cts_flag(&mut self) {
+        self.icr.clear_cts();
+    }
+
     /// Clear the IDLE flag. IDLE flag is set when an idle line is detected. :P
     pub fn clear_idle_flag(&mut self) {
         self.icr.clear_idle();
     }
+
+    /// The address of the transmit data register, for the DMA to write to.
+    pub fn tdr_addr(&self) -> *const u32 {
+        &self.tdr as *const TDR as *const u32
+    }
+}
+
+/// Set up a pin carrying a Usart signal (TX, RX, nRTS or nCTS).
+///
+/// The pin has already been connected to the Usart by its alternate function, this sets the
+/// speed and pull up the signal needs.
+pub fn connect_pin<P: SignalPin>(pin: &mut P) {
+    pin.configure(gpio::Speed::High, gpio::Type::PushPull, gpio::Pull::Up);
 }
 
-/// Initialize the Usart2 peripheral.
+/// Open the Usart `U` on the given TX and RX pins.
 ///
-/// Connects the necessary GPIO pins, sets the clock, enables interrupts,
-/// and currently configures the Usart2 to 9600 8N1 configuration.
-pub fn init() {
-    let mut rcc = rcc::rcc();
-    rcc.enable_peripheral(rcc::Peripheral::USART2);
-
-    gpio::GPIO::enable(gpio::Group::A);
-    let mut pa2 = gpio::Port::new(2, gpio::Group::A);
-    let mut pa15 = gpio::Port::new(15, gpio::Group::A);
-    pa2.set_function(gpio::AlternateFunction::One);
-    pa15.set_function(gpio::AlternateFunction::One);
-    pa2.set_speed(gpio::Speed::High);
-    pa15.set_speed(gpio::Speed::High);
-    pa2.set_mode(gpio::Mode::Alternate);
-    pa15.set_mode(gpio::Mode::Alternate);
-    pa2.set_type(gpio::Type::PushPull);
-    pa15.set_type(gpio::Type::PushPull);
-    pa2.set_pull(gpio::Pull::Up);
-    pa15.set_pull(gpio::Pull::Up);
-
-    let mut usart2 = Usart::new(UsartX::Usart2);
-    usart2.disable_usart();
-
-    usart2.set_word_length(WordLength::Eight);
-    usart2.set_dma_mode(DMAMode::All);
-    usart2.set_mode(Mode::All);
-    usart2.set_parity(Parity::None);
-    usart2.set_hardware_flow_control(HardwareFlowControl::None);
-
-    let clock_rate = rcc.get_system_clock_rate();
-    usart2.set_baud_rate(BaudRate::Hz115200, clock_rate);
-
-    usart2.enable_receiver_not_empty_interrupt();
-    usart2.enable_transmit_interrupt();
-    usart2.enable_usart();
-
-    let mut nvic = interrupt::nvic();
-    nvic.enable_interrupt(interrupt::Hardware::Usart2);
+/// Sets up the pins, sets the clock, enables interrupts, and configures the Usart to the given
+/// baud rate with 8N1. The pins' types make sure they carry the signals of Usart `U`.
+///
+/// Each Usart can only be opened once, so the returned Usart is its only owner outside
+/// of the kernel.
+///
+/// Example Usage:
+/// ```
+///   let pins = peripherals.gpioa.split().pins;
+///   let tx = pins.p9.unwrap().into_alternate::<AF1>();
+///   let rx = pins.p10.unwrap().into_alternate::<AF1>();
+///   let usart1 = usart::open(tx, rx, BaudRate::Hz9600).unwrap();
+/// ```
+pub fn open<U, TX, RX>(mut tx: TX, mut rx: RX, baud_rate: BaudRate) -> Result<Usart, UsartError>
+    where U: UsartId, TX: UsartTx<U>, RX: UsartRx<U> {
+
+    let x = U::usart();
+    try!(claim(x));
+
+    // UNSAFE: Only the clock and interrupt for this Usart are changed
+    let (mut rcc, mut nvic) = unsafe { (rcc::rcc(), interrupt::nvic()) };
+    rcc.enable_peripheral(x.peripheral());
+
+    connect_pin(&mut tx);
+    connect_pin(&mut rx);
+
+    // UNSAFE: The Usart was just claimed, so this is its only owner
+    let mut usart = unsafe { Usart::new(x) };
+    usart.disable_usart();
+
+    usart.set_word_length(WordLength::Eight);
+    usart.set_dma_mode(DMAMode::All);
+    usart.set_mode(Mode::All);
+    usart.set_parity(Parity::None);
+    usart.set_hardware_flow_control(HardwareFlowControl::None);
+
+    let clock_rate = rcc.get_peripheral_clock_rate(x.peripheral());
+    usart.set_baud_rate(baud_rate, clock_rate);
+
+    usart.enable_receiver_not_empty_interrupt();
+    usart.enable_transmit_interrupt();
+    usart.enable_usart();
+
+    nvic.enable_interrupt(x.hardware());
+    Ok(usart)
+}
+
+// Mark the Usart as open, failing if it already was.
+fn claim(x: UsartX) -> Result<(), UsartError> {
+    let bit = 0b1 << x.index();
+    if OPEN.fetch_or(bit, Ordering::Relaxed) & bit != 0 {
+        return Err(UsartError::AlreadyOpen(x));
+    }
+    Ok(())
+}
+
+/// Initialize the console USART.
+///
+/// Has the board open its console Usart and records it as the console.
+#[cfg(feature="serial")]
+pub fn init(board: &Board) {
+    match board.open_console() {
+        Ok(x) => CONSOLE.store(x.index() + 1, Ordering::Relaxed),
+        Err(err) => panic!("usart::init - the console could not be opened: {:?}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usart2_channels_unchanged() {
+        assert_eq!(UsartX::Usart2.tx_chan(), 43);
+        assert_eq!(UsartX::Usart2.rx_chan(), 43 * 3);
+    }
+
+    #[test]
+    fn test_channels_are_unique() {
+        for a in UsartX::all() {
+            for b in UsartX::all() {
+                if a != b {
+                    assert!(a.tx_chan() != b.tx_chan());
+                    assert!(a.rx_chan() != b.rx_chan());
+                    assert!(a.tx_chan() != b.rx_chan());
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature="stm32f09x")]
+    fn test_usart3_plus_share_interrupt() {
+        for x in &UsartX::all()[2..] {
+            assert_eq!(x.hardware(), Hardware::Usart3Plus);
+        }
+    }
+
+    #[test]
+    fn test_all_usarts_are_indexed_in_order() {
+        assert_eq!(UsartX::all().len(), USART_COUNT);
+        for (i, x) in UsartX::all().iter().enumerate() {
+            assert_eq!(x.index(), i);
+        }
+    }
+
+    #[test]
+    fn test_usart_can_only_be_claimed_once() {
+        assert_eq!(claim(UsartX::Usart1), Ok(()));
+        assert!(is_open(UsartX::Usart1));
+        assert_eq!(claim(UsartX::Usart1), Err(UsartError::AlreadyOpen(UsartX::Usart1)));
+    }
 }
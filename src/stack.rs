@@ -0,0 +1,280 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! This module provides task stack overflow detection and stack usage statistics.
+//!
+//! A task's stack is painted with a known pattern when the task is created, the lowest
+//! `CANARY_WORDS` words of the stack act as canaries. The canaries are checked on every context
+//! switch along with the saved stack pointer, and if either shows that the task has run past the
+//! end of its stack the system panics, naming the task that overflowed.
+//!
+//! The high-water mark of a stack is found by counting how many words from the bottom of the stack
+//! still hold the paint pattern.
+//!
+//! Tasks must be created with `new_task` for their stacks to be painted. Tasks created directly
+//! through `altos_core` only have their stacks painted if their memory has not been used since
+//! the heap was initialized.
+
+use core::mem::size_of;
+use altos_core::{TaskHandle, TaskControl, Priority};
+use altos_core::args::Args;
+use altos_core::syscall;
+use altos_core::sync::CriticalSection;
+
+/// The pattern written to unused stack memory.
+pub const STACK_PAINT: usize = 0xA5A5_A5A5;
+
+/// The number of words at the bottom of each stack reserved as canaries.
+pub const CANARY_WORDS: usize = 4;
+
+const WORD_SIZE: usize = size_of::<usize>();
+
+extern "C" {
+    // The control block of the currently running task, managed by the kernel
+    static CURRENT_TASK: *const TaskControl;
+}
+
+/// Stack usage statistics for a task.
+#[derive(Copy, Clone, Debug)]
+pub struct StackUsage {
+    /// The size of the stack in bytes.
+    pub size: usize,
+    /// The most bytes of the stack that have been in use at one time.
+    pub high_water_mark: usize,
+}
+
+// A snapshot of a task's stack bounds, read from its control block
+struct TaskStack {
+    stack: usize,
+    stack_base: usize,
+    stack_depth: usize,
+}
+
+impl TaskStack {
+    fn of(handle: &TaskHandle) -> Option<Self> {
+        match (handle.stack_pointer(), handle.stack_base(), handle.stack_depth()) {
+            (Ok(stack), Ok(stack_base), Ok(stack_depth)) => Some(TaskStack {
+                stack: stack,
+                stack_base: stack_base,
+                stack_depth: stack_depth,
+            }),
+            _ => None,
+        }
+    }
+
+    fn top(&self) -> usize {
+        self.stack_base + self.stack_depth
+    }
+
+    fn guard_limit(&self) -> usize {
+        self.stack_base + CANARY_WORDS * WORD_SIZE
+    }
+
+    fn canaries_intact(&self) -> bool {
+        let canaries = self.stack_base as *const usize;
+        // UNSAFE: The canaries are the lowest words of the task's own stack
+        (0..CANARY_WORDS).all(|i| unsafe { *canaries.offset(i as isize) } == STACK_PAINT)
+    }
+
+    fn is_overflowed(&self) -> bool {
+        self.stack < self.guard_limit() || self.stack > self.top() || !self.canaries_intact()
+    }
+
+    fn usage(&self) -> StackUsage {
+        let words = self.stack_depth / WORD_SIZE;
+        let bottom = self.stack_base as *const usize;
+        // UNSAFE: Only reading within the bounds of the task's own stack
+        let unused = (0..words)
+            .take_while(|&i| unsafe { *bottom.offset(i as isize) } == STACK_PAINT)
+            .count();
+
+        StackUsage {
+            size: self.stack_depth,
+            high_water_mark: (words - unused) * WORD_SIZE,
+        }
+    }
+
+    fn paint_unused(&self) {
+        // Everything below the saved stack pointer is unused when the task is created
+        paint(self.stack_base, self.stack - self.stack_base);
+    }
+}
+
+/// Create a new task with a painted stack.
+///
+/// This has the same arguments as `altos_core::syscall::new_task`, but paints the unused part of
+/// the new task's stack and its canaries before the task can run, so its stack usage can be
+/// tracked and overflows detected.
+pub fn new_task(code: fn(&mut Args), args: Args, stack_depth: usize, priority: Priority,
+    name: &'static str) -> TaskHandle {
+
+    // Tasks always run in privileged thread mode. The Cortex-M0 has no CONTROL.nPRIV bit, and on
+    // a core that did, the kernel's thread mode critical sections (`cpsid`) would silently stop
+    // masking interrupts in unprivileged tasks.
+    if cfg!(feature = "svc") {
+        // The supervisor call handler creates the task with `sys_new_task`, which paints it. An
+        // `svc` can't be made with interrupts disabled, so no critical section here.
+        syscall::new_task(code, args, stack_depth, priority, name)
+    }
+    else {
+        // The new task must not be switched to before its stack is painted
+        let _g = CriticalSection::begin();
+        // UNSAFE: Interrupts are disabled
+        unsafe { sys_new_task(code, args, stack_depth, priority, name) }
+    }
+}
+
+// Creates the task through the kernel entry point and paints its stack. Interrupts must be
+// disabled, or this must be called from the supervisor call handler, so that the new task isn't
+// switched to before its stack is painted.
+#[doc(hidden)]
+pub unsafe fn sys_new_task(code: fn(&mut Args), args: Args, stack_depth: usize,
+    priority: Priority, name: &'static str) -> TaskHandle {
+
+    let handle = syscall::sys_new_task(code, args, stack_depth, priority, name);
+    if let Some(stack) = TaskStack::of(&handle) {
+        stack.paint_unused();
+    }
+    handle
+}
+
+/// Get the stack usage of a task.
+///
+/// Returns `None` if the task is no longer valid.
+pub fn task_usage(handle: &TaskHandle) -> Option<StackUsage> {
+    TaskStack::of(handle).map(|stack| stack.usage())
+}
+
+/// Get the stack usage of the currently running task.
+pub fn current_task_usage() -> Option<StackUsage> {
+    // UNSAFE: The current task is only changed during a context switch, during which no task
+    // code is running
+    unsafe {
+        if CURRENT_TASK.is_null() {
+            None
+        }
+        else {
+            task_usage(&TaskHandle::new(CURRENT_TASK))
+        }
+    }
+}
+
+/// Paint a region of memory with the stack paint pattern.
+///
+/// This is done for the heap on initialization, before anything has been allocated, and for each
+/// task's stack when it is created with `new_task`.
+pub fn paint(start: usize, size: usize) {
+    let words = size / WORD_SIZE;
+    let start = start as *mut usize;
+    for i in 0..words {
+        // UNSAFE: The caller guarantees that the region isn't in use
+        unsafe { *start.offset(i as isize) = STACK_PAINT };
+    }
+}
+
+// This should only get called by the PendSV handler, after the current task's context has been
+// saved to its stack
+#[doc(hidden)]
+#[no_mangle]
+pub unsafe extern "C" fn check_task_stack(task: *const TaskControl) {
+    if task.is_null() {
+        return;
+    }
+    let handle = TaskHandle::new(task);
+    let stack = match TaskStack::of(&handle) {
+        Some(stack) => stack,
+        None => return,
+    };
+    if stack.is_overflowed() {
+        panic!("Stack overflow in task '{}', stack is {:#x}..{:#x}, stack pointer was {:#x}",
+            handle.name().unwrap_or("<unknown>"), stack.stack_base, stack.top(), stack.stack);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn painted_stack() -> [usize; 16] {
+        [STACK_PAINT; 16]
+    }
+
+    fn task_for(stack: &[usize; 16], sp_word: usize) -> TaskStack {
+        let base = stack.as_ptr() as usize;
+        TaskStack {
+            stack: base + sp_word * WORD_SIZE,
+            stack_base: base,
+            stack_depth: 16 * WORD_SIZE,
+        }
+    }
+
+    #[test]
+    fn test_painted_stack_is_not_overflowed() {
+        let stack = painted_stack();
+        let task = task_for(&stack, 12);
+
+        assert!(!task.is_overflowed());
+    }
+
+    #[test]
+    fn test_stack_pointer_in_canaries_is_overflowed() {
+        let stack = painted_stack();
+        let task = task_for(&stack, 2);
+
+        assert!(task.is_overflowed());
+    }
+
+    #[test]
+    fn test_corrupted_canary_is_overflowed() {
+        let mut stack = painted_stack();
+        stack[1] = 0;
+        let task = task_for(&stack, 12);
+
+        assert!(task.is_overflowed());
+    }
+
+    #[test]
+    fn test_usage_counts_words_above_paint() {
+        let mut stack = painted_stack();
+        for word in stack[10..].iter_mut() {
+            *word = 0;
+        }
+        let task = task_for(&stack, 10);
+
+        let usage = task.usage();
+        assert_eq!(usage.size, 16 * WORD_SIZE);
+        assert_eq!(usage.high_water_mark, 6 * WORD_SIZE);
+    }
+
+    #[test]
+    fn test_paint_unused_stops_at_stack_pointer() {
+        let mut stack = [0; 16];
+        let base = stack.as_mut_ptr() as usize;
+        let task = TaskStack {
+            stack: base + 12 * WORD_SIZE,
+            stack_base: base,
+            stack_depth: 16 * WORD_SIZE,
+        };
+
+        task.paint_unused();
+
+        assert!(stack[..12].iter().all(|&word| word == STACK_PAINT));
+        assert!(stack[12..].iter().all(|&word| word == 0));
+        assert!(!task.is_overflowed());
+        assert_eq!(task.usage().high_water_mark, 4 * WORD_SIZE);
+    }
+}
@@ -0,0 +1,50 @@
+/*
+* Copyright (C) 2017 AltOS-Rust Team
+*
+* This program is free software: you can redistribute it and/or modify
+* it under the terms of the GNU General Public License as published by
+* the Free Software Foundation, either version 3 of the License, or
+* (at your option) any later version.
+*
+* This program is distributed in the hope that it will be useful,
+* but WITHOUT ANY WARRANTY; without even the implied warranty of
+* MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+* GNU General Public License for more details.
+*
+* You should have received a copy of the GNU General Public License
+* along with this program. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use super::defs::*;
+
+#[derive(Copy, Clone, Debug)]
+pub struct AIRCR(u32);
+
+impl AIRCR {
+    pub fn request_system_reset(&mut self) {
+        // Writes to this register are ignored unless the vector key is written along with them
+        self.0 = AIRCR_VECTKEY | AIRCR_SYSRESETREQ;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aircr_request_system_reset() {
+        let mut aircr = AIRCR(0);
+
+        aircr.request_system_reset();
+        assert_eq!(aircr.0, 0x05FA << 16 | 0b1 << 2);
+    }
+
+    #[test]
+    fn test_aircr_request_system_reset_overwrites_vector_key() {
+        // Reads of the key field return 0xFA05
+        let mut aircr = AIRCR(0xFA05 << 16);
+
+        aircr.request_system_reset();
+        assert_eq!(aircr.0, 0x05FA << 16 | 0b1 << 2);
+    }
+}
@@ -18,5 +18,10 @@
 pub const SCB_ADDR: *const u32 = 0xE000_ED00 as *const _;
 
 pub const ICSR_OFFSET: u32 = 0x04;
+pub const ICSR_VECTACTIVE_MASK: u32 = 0b11_1111;
 pub const ICSR_PENDSVCLR: u32 = 0b1 << 27;
 pub const ICSR_PENDSVSET: u32 = 0b1 << 28;
+
+pub const AIRCR_OFFSET: u32 = 0x0C;
+pub const AIRCR_VECTKEY: u32 = 0x05FA << 16;
+pub const AIRCR_SYSRESETREQ: u32 = 0b1 << 2;
@@ -28,6 +28,10 @@ impl ICSR {
     pub fn clear_pend_sv(&mut self) {
         self.0 |= ICSR_PENDSVCLR;
     }
+
+    pub fn get_active_vector(&self) -> u8 {
+        (self.0 & ICSR_VECTACTIVE_MASK) as u8
+    }
 }
 
 #[cfg(test)]
@@ -49,4 +53,12 @@ mod tests {
         icsr.clear_pend_sv();
         assert_eq!(icsr.0, 0b1 << 27);
     }
+
+    #[test]
+    fn test_icsr_get_active_vector() {
+        // PendSV set pending while in the USART2 handler (exception 44)
+        let icsr = ICSR(0b1 << 28 | 44);
+
+        assert_eq!(icsr.get_active_vector(), 44);
+    }
 }
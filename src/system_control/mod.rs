@@ -19,11 +19,13 @@
 //! configuration control and reporting of system exceptions.
 
 mod icsr;
+mod aircr;
 mod defs;
 
 use core::ops::{Deref, DerefMut};
 use ::volatile::Volatile;
 use self::icsr::ICSR;
+use self::aircr::AIRCR;
 use self::defs::*;
 
 /// Returns instance of the System Control Block.
@@ -38,7 +40,7 @@ pub struct RawSCB {
     cpuid: u32,
     icsr: ICSR,
     reserved1: u32,
-    aircr: u32,
+    aircr: AIRCR,
     scr: u32,
     ccr: u32,
     reserved2: u32,
@@ -85,4 +87,20 @@ impl RawSCB {
     pub fn clear_pend_sv(&mut self) {
         self.icsr.clear_pend_sv();
     }
+
+    /// Get the exception number of the currently active exception.
+    ///
+    /// Returns 0 if the processor is in thread mode. Exception numbers 16 and above correspond to
+    /// the external interrupts, with exception 16 being interrupt 0.
+    pub fn get_active_vector(&self) -> u8 {
+        self.icsr.get_active_vector()
+    }
+
+    /// Request a system level reset.
+    ///
+    /// The reset is not instantaneous, so the caller should not expect any code after this call
+    /// to be skipped. Follow this with a barrier and a loop if execution must not continue.
+    pub fn request_system_reset(&mut self) {
+        self.aircr.request_system_reset();
+    }
 }